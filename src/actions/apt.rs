@@ -5,7 +5,7 @@ use tokio::stream::StreamExt;
 use tokio::io::{BufReader, AsyncBufReadExt};
 
 use crate::result::Result;
-use super::{write_file, run_cmd, CmdOpts};
+use super::{write_file, file_changed, run_cmd, CmdOpts};
 use crate::config::apt::AptRepository;
 
 pub async fn update_packages() -> Result<()> {
@@ -17,29 +17,47 @@ pub async fn update_packages() -> Result<()> {
     }).await
 }
 
-pub async fn add_repository(repo: &AptRepository) -> Result<()> {
-    let tmpl = format!(
-        "deb [signed-by=/usr/share/keyrings/{}.gpg] {} {} {}\n",
-        repo.name,
-        repo.url,
-        repo.distro,
-        repo.components.join(" "),
-    );
-
-    if write_file(
+pub fn repository_files(repo: &AptRepository) -> (String, String) {
+    (
         format!("/etc/apt/sources.list.d/{}.list", repo.name),
-        tmpl.as_bytes(),
-    ).await? {
+        format!("/usr/share/keyrings/{}.gpg", repo.name),
+    )
+}
+
+fn repository_source(repo: &AptRepository) -> (String, String) {
+    (
+        repository_files(repo).0,
+        format!(
+            "deb [signed-by=/usr/share/keyrings/{}.gpg] {} {} {}\n",
+            repo.name,
+            repo.url,
+            repo.distro,
+            repo.components.join(" "),
+        ),
+    )
+}
+
+pub async fn repository_changed(repo: &AptRepository) -> Result<bool> {
+    let (filename, tmpl) = repository_source(repo);
+
+    file_changed(filename, tmpl.as_bytes()).await
+}
+
+pub async fn add_repository(repo: &AptRepository) -> Result<bool> {
+    let (filename, tmpl) = repository_source(repo);
+
+    if write_file(filename, tmpl.as_bytes()).await? {
         let body = reqwest::get(&repo.key_url)
             .await?
             .bytes()
             .await?;
 
-        fs::write(format!("/usr/share/keyrings/{}.gpg", repo.name), body).await?;
+        fs::write(repository_files(repo).1, body).await?;
+
+        return Ok(true);
     }
 
-    println!("no changes found for apt repository: {}", repo.name);
-    Ok(())
+    Ok(false)
 }
 
 pub fn list_installed_packages() -> impl Stream<Item = Result<String>> {
@@ -66,38 +84,46 @@ pub fn list_installed_packages() -> impl Stream<Item = Result<String>> {
     }
 }
 
-pub async fn install_packages(names: &Vec<String>) -> Result<()> {
-    let mut to_be_installed = names.clone();
+pub async fn remove_packages(names: &[String]) -> Result<()> {
+    run_cmd(CmdOpts {
+        name: "apt-get",
+        args: &[&["remove", "-y"][..], &names
+            .iter()
+            .map(|pkg| pkg.as_str())
+            .collect::<Vec<_>>()[..],
+        ].concat(),
+        inherit_output: true,
+        ..CmdOpts::default()
+    }).await
+}
+
+pub async fn missing_packages(names: &[String]) -> Result<Vec<String>> {
+    let mut to_be_installed = names.to_vec();
     let packages = list_installed_packages();
     tokio::pin!(packages);
 
     while let Some(Ok(package)) = packages.next().await {
-        if to_be_installed.len() == 0 {
+        if to_be_installed.is_empty() {
             break;
         }
 
         to_be_installed.retain(|pkg| pkg != &package);
     }
 
-    if to_be_installed.len() == 0 {
-        println!("no changes found for apt packages");
-        return Ok(());
-    }
+    Ok(to_be_installed)
+}
 
-    println!("updating packages");
+pub async fn install_packages(names: &[String]) -> Result<()> {
     update_packages().await?;
 
-    println!("installing packages: {}", to_be_installed.join(", "));
     run_cmd(CmdOpts {
         name: "apt-get",
-        args: &[&["install", "--no-install-recommends", "-y"][..], &to_be_installed
+        args: &[&["install", "--no-install-recommends", "-y"][..], &names
             .iter()
             .map(|pkg| pkg.as_str())
             .collect::<Vec<_>>()[..],
         ].concat(),
         inherit_output: true,
         ..CmdOpts::default()
-    }).await?;
-
-    Ok(())
+    }).await
 }
@@ -1,12 +1,13 @@
-use std::env;
 use std::path::Path;
 use std::process::Stdio;
 use std::env::current_dir;
 
 use tokio::process::Command;
 
+use crate::ioutils::sudo_id;
 use crate::result::{Result, Error};
 use crate::config::scripts::Script;
+use crate::config::handlers::Handler;
 
 pub struct CmdOpts<'a> {
     pub name: &'a str,
@@ -28,16 +29,15 @@ impl<'a> Default for CmdOpts<'a> {
     }
 }
 
-pub async fn run_cmd(opts: CmdOpts<'_>) -> Result<()> {
+pub(super) fn command(opts: &CmdOpts<'_>) -> Result<Command> {
     log::info!("running: {} {}", opts.name, opts.args.join(" "));
 
-    let status = Command::new(opts.name)
+    let mut cmd = Command::new(opts.name);
+
+    cmd
         .uid(match opts.privileged {
             true => 0,
-            false => env::var("SUDO_UID")
-                .unwrap()
-                .parse()
-                .unwrap(),
+            false => sudo_id("SUDO_UID")?,
         })
         .args(opts.args)
         .stdin(Stdio::null())
@@ -50,9 +50,15 @@ pub async fn run_cmd(opts: CmdOpts<'_>) -> Result<()> {
             false => Stdio::null(),
         })
         .current_dir(match opts.cwd {
-            Some(dirname) => dirname.clone().into(),
-            None => current_dir().unwrap(),
-        })
+            Some(dirname) => dirname.into(),
+            None => current_dir()?,
+        });
+
+    Ok(cmd)
+}
+
+pub async fn run_cmd(opts: CmdOpts<'_>) -> Result<()> {
+    let status = command(&opts)?
         .spawn()?
         .await?;
 
@@ -65,18 +71,34 @@ pub async fn run_cmd(opts: CmdOpts<'_>) -> Result<()> {
     Ok(())
 }
 
-pub async fn run_script(script: &Script) -> Result<()> {
-    if let Ok(_) = run_cmd(CmdOpts {
+pub async fn cmd_output(opts: CmdOpts<'_>) -> Result<String> {
+    let output = command(&opts)?
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .output()
+        .await?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+
+        return Err(Error::Custom(match stderr.trim() {
+            "" => format!("failed to run {}, status code: {}", opts.name, output.status.code().unwrap()),
+            stderr => format!("failed to run {}, status code: {}: {}", opts.name, output.status.code().unwrap(), stderr),
+        }));
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+pub async fn script_needed(script: &Script) -> bool {
+    run_cmd(CmdOpts {
         name: "bash",
         args: &["-c", &script.test],
         ..CmdOpts::default()
-    }).await {
-        println!("no changes found for script: {}", script.name);
-        return Ok(());
-    }
-
-    println!("running script: {}", script.name);
+    }).await.is_err()
+}
 
+pub async fn run_script(script: &Script) -> Result<()> {
     run_cmd(CmdOpts {
         name: "bash",
         args: &["-c", &script.cmd],
@@ -85,3 +107,13 @@ pub async fn run_script(script: &Script) -> Result<()> {
         ..CmdOpts::default()
     }).await
 }
+
+pub async fn run_handler(handler: &Handler) -> Result<()> {
+    run_cmd(CmdOpts {
+        name: "bash",
+        args: &["-c", &handler.cmd],
+        privileged: handler.privileged,
+        inherit_output: true,
+        ..CmdOpts::default()
+    }).await
+}
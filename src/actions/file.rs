@@ -1,37 +1,79 @@
+use std::str;
 use std::marker::Unpin;
 use std::path::{Path, PathBuf};
 
 use crc32fast::Hasher;
+use similar::TextDiff;
 use tokio::fs::{self, File, OpenOptions};
 use tera::{Tera, Context, Map, Value};
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
 
+use crate::ioutils::{chmod, chown, owner_and_mode};
 use crate::result::{Error, Result};
 
-fn checksum(buf: &[u8]) -> u32 {
+pub fn checksum(buf: &[u8]) -> u32 {
     let mut hasher = Hasher::new();
 
     hasher.update(buf);
     hasher.finalize()
 }
 
-pub async fn write_file<P, S>(filename: P, source: S) -> Result<bool>
-where P: AsRef<Path>, S: AsRef<[u8]> + Unpin {
-    let right_checksum = checksum(source.as_ref());
+fn unified_diff(filename: &Path, left: &[u8], right: &[u8]) -> String {
+    let (left, right) = match (str::from_utf8(left), str::from_utf8(right)) {
+        (Ok(left), Ok(right)) => (left, right),
+        _ => return format!("binary file {} differs\n", filename.to_string_lossy()),
+    };
+
+    let name = filename.to_string_lossy();
 
-    if filename.as_ref().exists() {
-        let mut left_file = File::open(filename.as_ref()).await?;
+    TextDiff::from_lines(left, right)
+        .unified_diff()
+        .header(&format!("a{}", name), &format!("b{}", name))
+        .to_string()
+}
+
+pub async fn file_checksum<P>(filename: P) -> Result<Option<u32>>
+where P: AsRef<Path> {
+    if !filename.as_ref().exists() {
+        return Ok(None);
+    }
 
-        let mut left_buffer = Vec::new();
+    Ok(Some(checksum(&fs::read(filename).await?)))
+}
+
+async fn read_changed(filename: &Path, source: &[u8]) -> Result<Option<Vec<u8>>> {
+    let mut left_buffer = Vec::new();
+
+    if filename.exists() {
+        let mut left_file = File::open(filename).await?;
         left_file.read_to_end(&mut left_buffer).await?;
 
-        if right_checksum == checksum(&left_buffer) {
-            println!("no changes found for: {}", filename.as_ref().to_string_lossy());
-            return Ok(false);
+        if checksum(source) == checksum(&left_buffer) {
+            return Ok(None);
         }
     }
 
-    println!("updating file: {}", filename.as_ref().to_string_lossy());
+    Ok(Some(left_buffer))
+}
+
+pub async fn file_changed<P, S>(filename: P, source: S) -> Result<bool>
+where P: AsRef<Path>, S: AsRef<[u8]> {
+    Ok(read_changed(filename.as_ref(), source.as_ref()).await?.is_some())
+}
+
+/// Returns a unified diff between the file and the new contents if they differ
+pub async fn file_diff<P, S>(filename: P, source: S) -> Result<Option<String>>
+where P: AsRef<Path>, S: AsRef<[u8]> {
+    Ok(read_changed(filename.as_ref(), source.as_ref())
+        .await?
+        .map(|left_buffer| unified_diff(filename.as_ref(), &left_buffer, source.as_ref())))
+}
+
+pub async fn write_file<P, S>(filename: P, source: S) -> Result<bool>
+where P: AsRef<Path>, S: AsRef<[u8]> + Unpin {
+    if read_changed(filename.as_ref(), source.as_ref()).await?.is_none() {
+        return Ok(false);
+    }
 
     let mut dir_name = PathBuf::from(filename.as_ref());
     dir_name.pop();
@@ -59,13 +101,58 @@ where P: AsRef<Path>, S: AsRef<[u8]> + Unpin {
     Ok(true)
 }
 
-pub async fn write_template<P, S>(template_name: &str, filename: P, source: S, ctx: Map<String, Value>) -> Result<bool>
-where P: AsRef<Path>, S: AsRef<str> {
-    let contents = Tera::one_off(
+pub async fn replace_file<P>(source: P, filename: &str, owner: Option<(u32, u32)>, mode: Option<u32>) -> Result<()>
+where P: AsRef<Path> {
+    let mut dir_name = PathBuf::from(filename);
+    dir_name.pop();
+
+    if !dir_name.exists() {
+        fs::create_dir_all(dir_name).await?;
+    }
+
+    let existing = if Path::new(filename).exists() {
+        Some(owner_and_mode(filename).await?)
+    } else {
+        None
+    };
+    let tmp_name = format!("{}.compute-tmp", filename);
+
+    fs::copy(source, &tmp_name).await?;
+
+    // keep the owner and mode of the file being replaced, like an in-place write would
+    if let Some((uid, gid, existing_mode)) = existing {
+        if owner.is_none() {
+            chown(&tmp_name, uid, gid).await?;
+        }
+
+        chmod(&tmp_name, existing_mode).await?;
+    }
+
+    let mut perms = fs::metadata(&tmp_name)
+        .await?
+        .permissions();
+    perms.set_readonly(true);
+
+    fs::set_permissions(&tmp_name, perms).await?;
+
+    if let Some((uid, gid)) = owner {
+        chown(&tmp_name, uid, gid).await?;
+    }
+
+    if let Some(mode) = mode {
+        chmod(&tmp_name, mode).await?;
+    }
+
+    fs::rename(tmp_name, filename).await?;
+
+    Ok(())
+}
+
+pub fn render_template<S>(template_name: &str, source: S, ctx: Map<String, Value>) -> Result<String>
+where S: AsRef<str> {
+    Tera::one_off(
         source.as_ref(),
         &Context::from_value(Value::Object(ctx))?,
         false,
-    ).map_err(|e| Error::from_template_err(template_name, e))?;
-
-    write_file(filename, contents.as_bytes()).await
+    ).map_err(|e| Error::from_template_err(template_name, e))
 }
@@ -0,0 +1,172 @@
+use std::path::{Path, PathBuf};
+
+use tokio::fs;
+
+use crate::result::Result;
+use crate::ioutils::state_root;
+use super::{checksum, run_cmd, cmd_output, CmdOpts};
+
+async fn git(cwd: &Path, args: &[&str]) -> Result<String> {
+    cmd_output(CmdOpts {
+        name: "git",
+        args,
+        cwd: Some(cwd),
+        ..CmdOpts::default()
+    }).await
+}
+
+async fn has_commit(mirror: &Path, commit: &str) -> bool {
+    git(mirror, &["cat-file", "-e", &format!("{}^{{commit}}", commit)])
+        .await
+        .is_ok()
+}
+
+pub async fn fetch_repository(url: &str, reference: Option<&str>, commit: Option<&str>) -> Result<(PathBuf, String)> {
+    let cache = state_root().join("modules");
+    let id = format!("{:08x}", checksum(url.as_bytes()));
+    let mirror = cache.join("repos").join(format!("{}.git", id));
+
+    let fresh = !mirror.exists();
+
+    if fresh {
+        log::info!("cloning module repository: {}", url);
+
+        fs::create_dir_all(cache.join("repos")).await?;
+        run_cmd(CmdOpts {
+            name: "git",
+            args: &["clone", "--quiet", "--mirror", url, &mirror.to_string_lossy()],
+            inherit_output: true,
+            ..CmdOpts::default()
+        }).await?;
+    }
+
+    let commit = match commit {
+        Some(commit) => {
+            if !has_commit(&mirror, commit).await {
+                git(&mirror, &["fetch", "--quiet", "--prune"]).await?;
+            }
+
+            commit.to_string()
+        },
+        None => {
+            if !fresh {
+                git(&mirror, &["fetch", "--quiet", "--prune"]).await?;
+            }
+
+            git(&mirror, &["rev-parse", &format!("{}^{{commit}}", reference.unwrap_or("HEAD"))]).await?
+        },
+    };
+
+    let checkout = cache.join("checkouts").join(format!("{}-{}", id, commit));
+
+    if !checkout.exists() {
+        let tmp_checkout = cache.join("checkouts").join(format!("{}-{}.tmp", id, commit));
+
+        if tmp_checkout.exists() {
+            fs::remove_dir_all(&tmp_checkout).await?;
+        }
+
+        fs::create_dir_all(cache.join("checkouts")).await?;
+        git(&cache, &["clone", "--quiet", "--no-checkout", &mirror.to_string_lossy(), &tmp_checkout.to_string_lossy()]).await?;
+        git(&tmp_checkout, &["checkout", "--quiet", "--detach", &commit]).await?;
+        fs::rename(&tmp_checkout, &checkout).await?;
+    }
+
+    Ok((checkout, commit))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+    use std::path::Path;
+    use std::process::Command;
+
+    use super::fetch_repository;
+    use crate::ioutils::getuid;
+    use crate::lockfile::Lockfile;
+    use crate::config::sources::ModuleSource;
+
+    fn git(cwd: &Path, args: &[&str]) -> String {
+        let output = Command::new("git")
+            .args(["-c", "user.name=compute", "-c", "user.email=compute@localhost"])
+            .args(args)
+            .current_dir(cwd)
+            .output()
+            .unwrap();
+
+        assert!(output.status.success(), "git {}: {}", args.join(" "), String::from_utf8_lossy(&output.stderr));
+
+        String::from_utf8_lossy(&output.stdout).trim().to_string()
+    }
+
+    fn push_commit(work: &Path, message: &str) -> String {
+        fs::write(work.join("module.json5"), message).unwrap();
+        git(work, &["add", "-A"]);
+        git(work, &["commit", "--quiet", "-m", message]);
+        git(work, &["push", "--quiet", "origin", "HEAD"]);
+        git(work, &["rev-parse", "HEAD"])
+    }
+
+    #[tokio::test]
+    async fn fetches_pinned_commits_from_a_cached_mirror() {
+        // git runs as root (like every privileged command) so this only works when running as root
+        if getuid() != 0 {
+            return;
+        }
+
+        let root = env::temp_dir().join(format!("compute-git-test-{}", std::process::id()));
+        let origin = root.join("origin.git");
+        let work = root.join("work");
+        let url = origin.to_string_lossy().to_string();
+
+        fs::create_dir_all(&root).unwrap();
+        env::set_var("COMPUTE_STATE_ROOT", root.join("state"));
+        git(&root, &["init", "--quiet", "--bare", &url]);
+        git(&root, &["clone", "--quiet", &url, &work.to_string_lossy()]);
+
+        let first = push_commit(&work, "first");
+        let (checkout, commit) = fetch_repository(&url, None, None).await.unwrap();
+
+        assert_eq!(commit, first);
+        assert_eq!(fs::read_to_string(checkout.join("module.json5")).unwrap(), "first");
+
+        let source = ModuleSource {
+            git: url.clone(),
+            reference: None,
+            path: None,
+        };
+        let lockfile_path = root.join("compute.lock");
+        let mut lockfile = Lockfile::default();
+
+        lockfile.lock("test", &source, &commit);
+        lockfile.save(&lockfile_path).await.unwrap();
+
+        let lockfile = Lockfile::load(&lockfile_path).await.unwrap();
+
+        assert_eq!(lockfile.locked_commit("test", &source), Some(first.as_str()));
+
+        // a marker in the mirror shows that later fetches reuse it instead of cloning again
+        let mirrors = fs::read_dir(root.join("state/modules/repos"))
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .collect::<Vec<_>>();
+
+        assert_eq!(mirrors.len(), 1);
+        fs::write(mirrors[0].join("compute-test-marker"), "").unwrap();
+
+        let second = push_commit(&work, "second");
+        let (pinned_checkout, pinned) = fetch_repository(&url, None, Some(&first)).await.unwrap();
+
+        assert_eq!(pinned, first);
+        assert_eq!(pinned_checkout, checkout);
+
+        let (latest_checkout, latest) = fetch_repository(&url, None, None).await.unwrap();
+
+        assert_eq!(latest, second);
+        assert_eq!(fs::read_to_string(latest_checkout.join("module.json5")).unwrap(), "second");
+        assert!(mirrors[0].join("compute-test-marker").exists());
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -1,9 +1,16 @@
 mod s3;
 mod apt;
+mod git;
 mod file;
 mod exec;
+mod plugin;
 
 pub use s3::download_file;
-pub use exec::{run_cmd, run_script, CmdOpts};
-pub use file::{write_file, write_template};
-pub use apt::{add_repository, update_packages, install_packages, list_installed_packages};
+pub use git::fetch_repository;
+pub use plugin::run_plugin;
+pub use exec::{run_cmd, cmd_output, run_script, run_handler, script_needed, CmdOpts};
+pub use file::{checksum, write_file, replace_file, file_changed, file_diff, file_checksum, render_template};
+pub use apt::{
+    add_repository, repository_changed, repository_files,
+    install_packages, missing_packages, remove_packages,
+};
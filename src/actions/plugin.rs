@@ -0,0 +1,43 @@
+use std::path::Path;
+use std::process::Stdio;
+
+use tera::Value;
+use tokio::io::AsyncWriteExt;
+
+use crate::result::{Result, Error};
+use super::exec::command;
+use super::CmdOpts;
+
+pub async fn run_plugin(executable: &Path, privileged: bool, request: &Value) -> Result<Value> {
+    let name = executable.to_string_lossy();
+    let mut child = command(&CmdOpts {
+        name: &name,
+        privileged,
+        ..CmdOpts::default()
+    })?
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::inherit())
+        .spawn()?;
+
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(serde_json::to_string(request)?.as_bytes()).await?;
+    }
+
+    let output = child.wait_with_output().await?;
+
+    if !output.status.success() {
+        return Err(Error::Custom(
+            format!("failed to run plugin {}, status code: {:?}", name, output.status.code()),
+        ));
+    }
+
+    let response: Value = serde_json::from_slice(&output.stdout)
+        .map_err(|e| Error::Custom(format!("invalid response from plugin {}: {}", name, e)))?;
+
+    if let Some(error) = response.get("error").and_then(|error| error.as_str()) {
+        return Err(Error::Custom(format!("plugin {} failed: {}", name, error)));
+    }
+
+    Ok(response)
+}
@@ -0,0 +1,34 @@
+use std::fmt;
+
+use serde::{Serialize, Deserialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum Change {
+    Repository { name: String },
+    Packages { names: Vec<String> },
+    Script { name: String },
+    File { path: String, checksum: Option<u32> },
+    Handler { name: String },
+    Resource { kind: String, name: String },
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Repository { name } => write!(f, "add apt repository: {}", name),
+            Self::Packages { names } => write!(f, "install packages: {}", names.join(", ")),
+            Self::Script { name } => write!(f, "run script: {}", name),
+            Self::File { path, .. } => write!(f, "write file: {}", path),
+            Self::Handler { name } => write!(f, "run handler: {}", name),
+            Self::Resource { kind, name } => write!(f, "apply {}: {}", kind, name),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Record {
+    pub module: String,
+    #[serde(flatten)]
+    pub change: Change,
+}
@@ -13,12 +13,50 @@ pub struct AptRepository {
     pub key_url: String,
     #[serde(default = "main_component")]
     pub components: Vec<String>,
+    #[serde(default)]
+    pub when: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum Package {
+    Name(String),
+    Detailed {
+        name: String,
+        #[serde(default)]
+        when: Option<String>,
+        #[serde(default)]
+        tags: Vec<String>,
+    },
+}
+
+impl Package {
+    pub fn name(&self) -> &str {
+        match self {
+            Self::Name(name) => name,
+            Self::Detailed { name, .. } => name,
+        }
+    }
+
+    pub fn when(&self) -> Option<&str> {
+        match self {
+            Self::Name(_) => None,
+            Self::Detailed { when, .. } => when.as_deref(),
+        }
+    }
+
+    pub fn tags(&self) -> &[String] {
+        match self {
+            Self::Name(_) => &[],
+            Self::Detailed { tags, .. } => tags,
+        }
+    }
 }
 
 #[derive(Debug, Default, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Config {
-    pub packages: Vec<String>,
+    pub packages: Vec<Package>,
     #[serde(default)]
     pub repositories: Vec<AptRepository>,
 }
@@ -25,10 +25,16 @@ pub struct TemplateFile {
     pub mode: Option<u32>,
     #[serde(default)]
     pub context: Map<String, Value>,
+    #[serde(default)]
+    pub notify: Vec<String>,
+    #[serde(default)]
+    pub when: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
 }
 
 impl TemplateFile {
-    pub fn source(&self) -> TemplateSource {
+    pub fn source(&self) -> TemplateSource<'_> {
         if let Some(template) = &self.template {
             return TemplateSource::Local(template);
         }
@@ -0,0 +1,12 @@
+use serde::{Serialize, Deserialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Handler {
+    pub name: String,
+    pub cmd: String,
+    #[serde(default)]
+    pub privileged: bool,
+}
+
+pub type Config = Vec<Handler>;
@@ -0,0 +1,346 @@
+use std::fs;
+use std::env;
+use std::path::{Path, PathBuf};
+
+use tera::{Tera, Map, Value};
+
+use super::Config;
+use crate::result::{Result, Error};
+use crate::facts::template_context;
+
+// lists of resources are combined when configs are merged, every other value is replaced
+const CONCATENATED: &[&str] = &[
+    "apt.packages",
+    "apt.repositories",
+    "files",
+    "scripts",
+    "handlers",
+    "modules",
+    "resources",
+];
+
+fn concatenated(path: &[String]) -> bool {
+    let path = match path {
+        [profiles, _, rest @ ..] if profiles == "profiles" => rest,
+        path => path,
+    };
+
+    CONCATENATED.contains(&path.join(".").as_str())
+}
+
+fn merge_at(path: &mut Vec<String>, left: &mut Value, right: Value) {
+    match (left, right) {
+        (Value::Object(left), Value::Object(right)) => {
+            for (key, value) in right {
+                match left.get_mut(&key) {
+                    Some(existing) => {
+                        path.push(key);
+                        merge_at(path, existing, value);
+                        path.pop();
+                    },
+                    None => {
+                        left.insert(key, value);
+                    },
+                }
+            }
+        },
+        (Value::Array(left), Value::Array(right)) if concatenated(path) => left.extend(right),
+        (left, right) => *left = right,
+    }
+}
+
+fn merge(left: &mut Value, right: Value) {
+    merge_at(&mut vec![], left, right);
+}
+
+fn resolve_path(dir: &Path, value: &mut Value) {
+    if let Value::String(path) = value {
+        *path = dir.join(&path)
+            .to_string_lossy()
+            .to_string();
+    }
+}
+
+fn resolve_paths(dir: &Path, config: &mut Map<String, Value>) {
+    if let Some(Value::Array(files)) = config.get_mut("files") {
+        for file in files {
+            if let Some(template) = file.get_mut("template") {
+                resolve_path(dir, template);
+            }
+        }
+    }
+
+    for key in &["modulePaths", "pluginPaths"] {
+        if let Some(Value::Array(paths)) = config.get_mut(*key) {
+            for path in paths {
+                resolve_path(dir, path);
+            }
+        }
+    }
+
+    if let Some(Value::Object(sources)) = config.get_mut("moduleSources") {
+        for source in sources.values_mut() {
+            if let Some(git) = source.get_mut("git") {
+                if !git.as_str().is_some_and(|url| url.contains("://") || url.contains('@')) {
+                    resolve_path(dir, git);
+                }
+            }
+        }
+    }
+
+    if let Some(Value::Object(profiles)) = config.get_mut("profiles") {
+        for profile in profiles.values_mut() {
+            if let Value::Object(profile) = profile {
+                resolve_paths(dir, profile);
+            }
+        }
+    }
+}
+
+pub fn load_vars(var_files: &[PathBuf], vars: &[String]) -> Result<Map<String, Value>> {
+    let mut merged = Map::new();
+
+    for (key, value) in env::vars() {
+        if let Some(name) = key.strip_prefix("COMPUTE_VAR_") {
+            merged.insert(name.to_string(), Value::String(value));
+        }
+    }
+
+    for filename in var_files {
+        log::info!("reading var file: {:?}", filename);
+
+        let contents = fs::read_to_string(filename)?;
+        let values = match filename.extension().and_then(|ext| ext.to_str()) {
+            Some("toml") => toml::from_str(&contents)?,
+            _ => json5::from_str(&contents)?,
+        };
+
+        match values {
+            Value::Object(values) => merged.extend(values),
+            _ => return Err(Error::Custom(format!("var file {} should contain an object", filename.to_string_lossy()))),
+        }
+    }
+
+    for var in vars {
+        let (key, value) = match var.find('=') {
+            Some(index) => (&var[..index], &var[index + 1..]),
+            None => return Err(Error::Custom(format!("invalid var {}, expected key=value", var))),
+        };
+
+        merged.insert(
+            key.to_string(),
+            json5::from_str(value).unwrap_or_else(|_| Value::String(value.to_string())),
+        );
+    }
+
+    Ok(merged)
+}
+
+pub struct ConfigContext<'a> {
+    pub vars: &'a Map<String, Value>,
+    pub facts: &'a Map<String, Value>,
+}
+
+fn render_config(filename: &Path, contents: &str, ctx: &ConfigContext<'_>) -> Result<String> {
+    let mut context = template_context(ctx.facts)?;
+
+    context.insert("vars", ctx.vars);
+
+    Tera::one_off(contents, &context, false)
+        .map_err(|e| Error::from_template_err(filename.to_string_lossy(), e))
+}
+
+fn read_config(filename: &Path, ctx: &ConfigContext<'_>, stack: &mut Vec<PathBuf>) -> Result<Value> {
+    let filename = filename.canonicalize()
+        .map_err(|e| Error::Custom(format!("failed to read config {}: {}", filename.to_string_lossy(), e)))?;
+
+    if stack.contains(&filename) {
+        return Err(Error::Custom(format!("config {} includes itself", filename.to_string_lossy())));
+    }
+
+    log::info!("reading config: {:?}", filename);
+
+    let contents = render_config(&filename, &fs::read_to_string(&filename)?, ctx)?;
+    let mut config = match json5::from_str(&contents)? {
+        Value::Object(config) => config,
+        _ => return Err(Error::Custom(format!("config {} should be an object", filename.to_string_lossy()))),
+    };
+
+    let mut dir = filename.clone();
+    dir.pop();
+
+    resolve_paths(&dir, &mut config);
+
+    let includes = match config.remove("include") {
+        Some(Value::Array(includes)) => includes,
+        Some(Value::String(include)) => vec![Value::String(include)],
+        Some(_) => return Err(Error::Custom(format!("invalid include in config {}", filename.to_string_lossy()))),
+        None => vec![],
+    };
+
+    stack.push(filename);
+
+    let mut merged = Value::Object(Map::new());
+
+    for include in includes {
+        match include {
+            Value::String(include) => merge(&mut merged, read_config(&dir.join(include), ctx, stack)?),
+            _ => return Err(Error::Custom(format!("invalid include in config {}", dir.to_string_lossy()))),
+        }
+    }
+
+    stack.pop();
+    merge(&mut merged, Value::Object(config));
+
+    Ok(merged)
+}
+
+pub fn load_config(filenames: &[PathBuf], profiles: &[String], ctx: &ConfigContext<'_>) -> Result<Config> {
+    let mut merged = Value::Object(Map::new());
+
+    for filename in filenames {
+        merge(&mut merged, read_config(filename, ctx, &mut vec![])?);
+    }
+
+    let mut available = match merged.as_object_mut().and_then(|config| config.remove("profiles")) {
+        Some(Value::Object(profiles)) => profiles,
+        Some(_) => return Err(Error::Custom("profiles should be an object".to_string())),
+        None => Map::new(),
+    };
+
+    for name in profiles {
+        log::info!("using profile: {}", name);
+
+        match available.remove(name) {
+            Some(profile) => merge(&mut merged, profile),
+            None => return Err(Error::Custom(format!("unknown profile {}", name))),
+        }
+    }
+
+    Ok(serde_json::from_value(merged)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+    use std::path::Path;
+
+    use tera::{Map, Value};
+
+    use super::{merge, resolve_paths, load_config, ConfigContext};
+    use crate::result::Error;
+
+    fn value(value: &str) -> Value {
+        json5::from_str(value).unwrap()
+    }
+
+    fn context<'a>(vars: &'a Map<String, Value>, facts: &'a Map<String, Value>) -> ConfigContext<'a> {
+        env::set_var("SUDO_UID", "0");
+        env::set_var("SUDO_GID", "0");
+
+        ConfigContext { vars, facts }
+    }
+
+    #[test]
+    fn merge_concatenates_resource_lists() {
+        let mut config = value("{
+            apt: { packages: ['git'], repositories: [{ name: 'a' }] },
+            files: [{ path: '/a' }],
+            modulePaths: ['a'],
+            networking: { hostname: 'a', dns: ['1.1.1.1'] },
+        }");
+
+        merge(&mut config, value("{
+            apt: { packages: ['curl'] },
+            files: [{ path: '/b' }],
+            modulePaths: ['b'],
+            networking: { dns: ['8.8.8.8'] },
+        }"));
+
+        assert_eq!(config, value("{
+            apt: { packages: ['git', 'curl'], repositories: [{ name: 'a' }] },
+            files: [{ path: '/a' }, { path: '/b' }],
+            modulePaths: ['b'],
+            networking: { hostname: 'a', dns: ['8.8.8.8'] },
+        }"));
+    }
+
+    #[test]
+    fn merge_concatenates_resource_lists_of_profiles() {
+        let mut config = value("{ profiles: { work: { scripts: [{ name: 'a' }], rust: { components: ['rustfmt'] } } } }");
+
+        merge(&mut config, value("{ profiles: { work: { scripts: [{ name: 'b' }], rust: { components: ['clippy'] } } } }"));
+
+        assert_eq!(config, value("{ profiles: { work: { scripts: [{ name: 'a' }, { name: 'b' }], rust: { components: ['clippy'] } } } }"));
+    }
+
+    #[test]
+    fn resolve_paths_relative_to_the_config() {
+        let mut config = value("{
+            files: [{ path: '/a', template: 'a.tpl' }],
+            modulePaths: ['modules'],
+            moduleSources: { local: { git: 'repos/local' }, remote: { git: 'https://example.com/modules.git' } },
+            profiles: { work: { files: [{ path: '/b', template: '/abs/b.tpl' }], pluginPaths: ['plugins'] } },
+        }");
+
+        resolve_paths(Path::new("/etc/compute"), config.as_object_mut().unwrap());
+
+        assert_eq!(config, value("{
+            files: [{ path: '/a', template: '/etc/compute/a.tpl' }],
+            modulePaths: ['/etc/compute/modules'],
+            moduleSources: { local: { git: '/etc/compute/repos/local' }, remote: { git: 'https://example.com/modules.git' } },
+            profiles: { work: { files: [{ path: '/b', template: '/abs/b.tpl' }], pluginPaths: ['/etc/compute/plugins'] } },
+        }"));
+    }
+
+    #[test]
+    fn load_config_merges_includes_and_profiles() {
+        let dir = env::temp_dir().join(format!("compute-loader-test-{}", std::process::id()));
+
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("base.json5"), "{
+            scripts: [{ name: 'base', test: 'true', cmd: 'true' }],
+            profiles: { work: { scripts: [{ name: 'work', test: 'true', cmd: 'true' }] } },
+        }").unwrap();
+        fs::write(dir.join("main.json5"), "{
+            include: 'base.json5',
+            scripts: [{ name: 'main', test: 'true', cmd: 'true' }],
+        }").unwrap();
+        fs::write(dir.join("extra.json5"), "{ scripts: [{ name: 'extra', test: 'true', cmd: 'true' }] }").unwrap();
+
+        let (vars, facts) = (Map::new(), Map::new());
+        let config = load_config(
+            &[dir.join("main.json5"), dir.join("extra.json5")],
+            &["work".to_string()],
+            &context(&vars, &facts),
+        ).unwrap();
+
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(
+            config.scripts.iter().map(|script| script.name.as_str()).collect::<Vec<_>>(),
+            vec!["base", "main", "extra", "work"],
+        );
+    }
+
+    #[test]
+    fn load_config_detects_self_includes() {
+        let dir = env::temp_dir().join(format!("compute-loader-include-test-{}", std::process::id()));
+
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("a.json5"), "{ include: ['b.json5'] }").unwrap();
+        fs::write(dir.join("b.json5"), "{ include: ['a.json5'] }").unwrap();
+
+        let (vars, facts) = (Map::new(), Map::new());
+        let result = load_config(&[dir.join("a.json5")], &[], &context(&vars, &facts));
+        let expected = format!("config {} includes itself", dir.join("a.json5").canonicalize().unwrap().to_string_lossy());
+
+        fs::remove_dir_all(&dir).unwrap();
+
+        match result {
+            Err(Error::Custom(message)) => assert_eq!(message, expected),
+            _ => panic!("expected an include cycle"),
+        }
+    }
+}
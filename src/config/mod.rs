@@ -4,7 +4,12 @@ use tera::{Map, Value};
 pub mod s3;
 pub mod apt;
 pub mod files;
+pub mod loader;
+pub mod modules;
+pub mod handlers;
 pub mod scripts;
+pub mod sources;
+pub mod resources;
 
 #[derive(Debug, Default, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -17,6 +22,35 @@ pub struct Config {
     pub scripts: scripts::Config,
     #[serde(default)]
     pub s3: s3::Config,
+    #[serde(default)]
+    pub handlers: handlers::Config,
+    #[serde(default)]
+    pub resources: resources::Config,
+    #[serde(default)]
+    pub module_paths: Vec<String>,
+    #[serde(default)]
+    pub plugin_paths: Vec<String>,
+    #[serde(default)]
+    pub module_sources: sources::Config,
+    #[serde(default, rename = "modules")]
+    pub instances: modules::Config,
     #[serde(default, flatten)]
     pub modules: Map<String, Value>,
 }
+
+impl Config {
+    pub fn module_instances(&self) -> Vec<modules::ModuleInstance> {
+        self.modules
+            .iter()
+            .map(|(name, vars)| modules::ModuleInstance {
+                module: name.clone(),
+                name: None,
+                vars: vars.clone(),
+                depends_on: vec![],
+                when: None,
+                tags: vec![],
+            })
+            .chain(self.instances.iter().cloned())
+            .collect()
+    }
+}
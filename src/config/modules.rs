@@ -0,0 +1,33 @@
+use tera::{Map, Value};
+use serde::Deserialize;
+
+fn empty_vars() -> Value {
+    Value::Object(Map::new())
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ModuleInstance {
+    #[serde(rename = "use")]
+    pub module: String,
+    #[serde(default, rename = "as")]
+    pub name: Option<String>,
+    #[serde(default = "empty_vars")]
+    pub vars: Value,
+    #[serde(default)]
+    pub depends_on: Vec<String>,
+    #[serde(default)]
+    pub when: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+impl ModuleInstance {
+    pub fn name(&self) -> &str {
+        self.name
+            .as_deref()
+            .unwrap_or(&self.module)
+    }
+}
+
+pub type Config = Vec<ModuleInstance>;
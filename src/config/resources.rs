@@ -0,0 +1,22 @@
+use tera::{Map, Value};
+use serde::Deserialize;
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CustomResource {
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub name: String,
+    #[serde(default)]
+    pub state: Map<String, Value>,
+    #[serde(default)]
+    pub privileged: bool,
+    #[serde(default)]
+    pub notify: Vec<String>,
+    #[serde(default)]
+    pub when: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+pub type Config = Vec<CustomResource>;
@@ -8,6 +8,14 @@ pub struct Script {
     pub cmd: String,
     #[serde(default)]
     pub privileged: bool,
+    #[serde(default)]
+    pub parallel: bool,
+    #[serde(default)]
+    pub notify: Vec<String>,
+    #[serde(default)]
+    pub when: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
 }
 
 pub type Config = Vec<Script>;
@@ -0,0 +1,83 @@
+use serde::Serialize;
+
+use super::changes::Change;
+use super::report::{Outcome, ResourceType};
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Stage {
+    Handlers,
+    Committing,
+    Restoring,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "event", rename_all = "camelCase")]
+pub enum Event {
+    #[serde(rename_all = "camelCase")]
+    RunStarted {
+        config: String,
+        dry_run: bool,
+    },
+    ModuleEntered {
+        name: String,
+        source: String,
+    },
+    /// The resource was compared with its desired state
+    ResourceChecked {
+        module: String,
+        #[serde(rename = "type")]
+        kind: ResourceType,
+        id: String,
+        changed: bool,
+    },
+    /// The resource is about to be changed
+    ResourceChanging {
+        module: String,
+        #[serde(rename = "type")]
+        kind: ResourceType,
+        id: String,
+        change: Change,
+    },
+    ResourceSkipped {
+        module: String,
+        #[serde(rename = "type")]
+        kind: ResourceType,
+        id: String,
+        reason: String,
+    },
+    /// The resource is done, its outcome tells whether it changed, was skipped or failed
+    ResourceFinished(Outcome),
+    Diff {
+        path: String,
+        diff: String,
+    },
+    StageStarted {
+        stage: Stage,
+    },
+    FileUpdated {
+        path: String,
+    },
+    FileRestored {
+        path: String,
+    },
+    #[serde(rename_all = "camelCase")]
+    RunFinished {
+        dry_run: bool,
+        success: bool,
+        error: Option<String>,
+        duration: f64,
+    },
+}
+
+/// Receives the events of a [`Provisioner`](super::Provisioner) run.
+pub trait Observer: Send + Sync {
+    fn on_event(&self, event: &Event);
+}
+
+impl<F> Observer for F
+where F: Fn(&Event) + Send + Sync {
+    fn on_event(&self, event: &Event) {
+        self(event)
+    }
+}
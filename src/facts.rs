@@ -0,0 +1,247 @@
+use std::fs;
+use std::env;
+use std::ffi::CStr;
+use std::convert::TryFrom;
+use std::net::{Ipv4Addr, Ipv6Addr};
+use std::collections::BTreeMap;
+
+use tera::{Context, Map, Value};
+
+use super::result::Result;
+use super::ioutils::{getuid, sudo_id};
+
+fn read_trimmed(filename: &str) -> Option<String> {
+    fs::read_to_string(filename)
+        .ok()
+        .map(|contents| contents.trim().to_string())
+}
+
+fn os_release() -> Map<String, Value> {
+    let contents = read_trimmed("/etc/os-release").unwrap_or_default();
+    let mut fields = BTreeMap::new();
+
+    for line in contents.lines() {
+        if let Some(index) = line.find('=') {
+            fields.insert(
+                &line[..index],
+                line[index + 1..].trim_matches('"').to_string(),
+            );
+        }
+    }
+
+    let mut os = Map::new();
+
+    for (key, field) in &[
+        ("id", "ID"),
+        ("idLike", "ID_LIKE"),
+        ("name", "NAME"),
+        ("prettyName", "PRETTY_NAME"),
+        ("version", "VERSION_ID"),
+        ("codename", "VERSION_CODENAME"),
+    ] {
+        os.insert(key.to_string(), match fields.get(field) {
+            Some(value) => Value::String(value.clone()),
+            None => Value::Null,
+        });
+    }
+
+    os
+}
+
+fn machine() -> String {
+    unsafe {
+        let mut uts: libc::utsname = std::mem::zeroed();
+
+        if libc::uname(&mut uts) != 0 {
+            return env::consts::ARCH.to_string();
+        }
+
+        CStr::from_ptr(uts.machine.as_ptr())
+            .to_string_lossy()
+            .to_string()
+    }
+}
+
+fn deb_arch(arch: &str) -> &str {
+    match arch {
+        "x86_64" => "amd64",
+        "aarch64" => "arm64",
+        arch if arch.starts_with("armv") => "armhf",
+        "i386" | "i486" | "i586" | "i686" => "i386",
+        arch => arch,
+    }
+}
+
+/// The architecture names used for Go downloads (`go1.16.linux-<goArch>.tar.gz`)
+fn go_arch(arch: &str) -> &str {
+    match arch {
+        "x86_64" => "amd64",
+        "aarch64" => "arm64",
+        arch if arch.starts_with("armv") => "armv6l",
+        "i386" | "i486" | "i586" | "i686" => "386",
+        arch => arch,
+    }
+}
+
+fn memory() -> Option<u64> {
+    read_trimmed("/proc/meminfo")?
+        .lines()
+        .find(|line| line.starts_with("MemTotal:"))?
+        .split_whitespace()
+        .nth(1)?
+        .parse::<u64>()
+        .ok()
+        .map(|kb| kb * 1024)
+}
+
+fn cpus() -> u64 {
+    let cpus = unsafe {
+        libc::sysconf(libc::_SC_NPROCESSORS_ONLN)
+    };
+
+    u64::try_from(cpus).unwrap_or(1)
+}
+
+fn user(uid: u32) -> Value {
+    let mut user = Map::new();
+
+    user.insert("uid".to_string(), Value::from(uid));
+
+    unsafe {
+        let passwd = libc::getpwuid(uid);
+
+        if !passwd.is_null() {
+            for (key, field) in &[
+                ("name", (*passwd).pw_name),
+                ("home", (*passwd).pw_dir),
+                ("shell", (*passwd).pw_shell),
+            ] {
+                if !field.is_null() {
+                    user.insert(key.to_string(), Value::String(
+                        CStr::from_ptr(*field)
+                            .to_string_lossy()
+                            .to_string(),
+                    ));
+                }
+            }
+
+            user.insert("gid".to_string(), Value::from((*passwd).pw_gid));
+        }
+    }
+
+    Value::Object(user)
+}
+
+fn addresses() -> BTreeMap<String, Vec<String>> {
+    let mut addresses: BTreeMap<String, Vec<String>> = BTreeMap::new();
+
+    unsafe {
+        let mut ifaddrs: *mut libc::ifaddrs = std::ptr::null_mut();
+
+        if libc::getifaddrs(&mut ifaddrs) != 0 {
+            return addresses;
+        }
+
+        let mut current = ifaddrs;
+
+        while !current.is_null() {
+            let ifaddr = &*current;
+            current = ifaddr.ifa_next;
+
+            if ifaddr.ifa_addr.is_null() {
+                continue;
+            }
+
+            let address = match (*ifaddr.ifa_addr).sa_family as i32 {
+                libc::AF_INET => {
+                    let addr = &*(ifaddr.ifa_addr as *const libc::sockaddr_in);
+                    Ipv4Addr::from(u32::from_be(addr.sin_addr.s_addr)).to_string()
+                },
+                libc::AF_INET6 => {
+                    let addr = &*(ifaddr.ifa_addr as *const libc::sockaddr_in6);
+                    Ipv6Addr::from(addr.sin6_addr.s6_addr).to_string()
+                },
+                _ => continue,
+            };
+
+            addresses
+                .entry(CStr::from_ptr(ifaddr.ifa_name).to_string_lossy().to_string())
+                .or_default()
+                .push(address);
+        }
+
+        libc::freeifaddrs(ifaddrs);
+    }
+
+    addresses
+}
+
+fn interfaces() -> Vec<Value> {
+    let mut addresses = addresses();
+    let mut names = fs::read_dir("/sys/class/net")
+        .map(|entries| entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.file_name().to_string_lossy().to_string())
+            .collect::<Vec<_>>())
+        .unwrap_or_default();
+
+    names.sort();
+    names
+        .into_iter()
+        .map(|name| {
+            let mut interface = Map::new();
+
+            interface.insert("mac".to_string(), match read_trimmed(&format!("/sys/class/net/{}/address", name)) {
+                Some(mac) => Value::String(mac),
+                None => Value::Null,
+            });
+            interface.insert("addresses".to_string(), Value::from(
+                addresses.remove(&name).unwrap_or_default(),
+            ));
+            interface.insert("name".to_string(), Value::String(name));
+
+            Value::Object(interface)
+        })
+        .collect()
+}
+
+pub fn gather() -> Map<String, Value> {
+    let uid = sudo_id("SUDO_UID").unwrap_or_else(|_| getuid());
+    let mut facts = Map::new();
+
+    log::info!("gathering facts");
+
+    facts.insert("os".to_string(), Value::Object(os_release()));
+    let arch = machine();
+
+    facts.insert("debArch".to_string(), Value::from(deb_arch(&arch)));
+    facts.insert("goArch".to_string(), Value::from(go_arch(&arch)));
+    facts.insert("arch".to_string(), Value::from(arch));
+    facts.insert("hostname".to_string(), Value::from(read_trimmed("/proc/sys/kernel/hostname")));
+    facts.insert("kernel".to_string(), Value::from(read_trimmed("/proc/sys/kernel/osrelease")));
+    facts.insert("cpus".to_string(), Value::from(cpus()));
+    facts.insert("memory".to_string(), Value::from(memory()));
+    facts.insert("user".to_string(), user(uid));
+    facts.insert("interfaces".to_string(), Value::from(interfaces()));
+
+    facts
+}
+
+/// The context every template starts from: the facts, the environment and the uid and gid of
+/// the user which is provisioned.
+pub fn template_context(facts: &Map<String, Value>) -> Result<Context> {
+    let mut env_vars = Map::new();
+
+    for (key, val) in env::vars() {
+        env_vars.insert(key, Value::String(val));
+    }
+
+    let mut context = Context::new();
+
+    context.insert("facts", facts);
+    context.insert("env", &Value::Object(env_vars));
+    context.insert("uid", &sudo_id("SUDO_UID")?);
+    context.insert("gid", &sudo_id("SUDO_GID")?);
+
+    Ok(context)
+}
@@ -0,0 +1,230 @@
+use std::path::{Path, PathBuf};
+
+use tokio::fs;
+use chrono::{Local, SecondsFormat};
+use serde::{Serialize, Deserialize, Deserializer};
+
+use super::actions;
+use super::changes::{Change, Record};
+use super::result::{Result, Error};
+use super::ioutils::{chmod, chown, owner_and_mode, state_root};
+
+pub fn history_root() -> PathBuf {
+    state_root().join("history")
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Backup {
+    pub path: String,
+    pub backup: Option<String>,
+    pub owner: Option<(u32, u32)>,
+    pub mode: Option<u32>,
+}
+
+impl Backup {
+    pub async fn create(dir: &Path, name: &str, path: &str) -> Result<Self> {
+        if !Path::new(path).exists() {
+            return Ok(Self {
+                path: path.to_string(),
+                backup: None,
+                owner: None,
+                mode: None,
+            });
+        }
+
+        let mut backup_path = PathBuf::from(dir);
+        backup_path.push("files");
+        fs::create_dir_all(&backup_path).await?;
+        backup_path.push(name);
+
+        fs::copy(path, &backup_path).await?;
+
+        let (uid, gid, mode) = owner_and_mode(path).await?;
+
+        Ok(Self {
+            path: path.to_string(),
+            backup: Some(name.to_string()),
+            owner: Some((uid, gid)),
+            mode: Some(mode),
+        })
+    }
+
+    pub async fn restore(&self, dir: &Path) -> Result<()> {
+        let name = match &self.backup {
+            Some(name) => name,
+            None => {
+                if Path::new(&self.path).exists() {
+                    log::info!("removing file: {}", self.path);
+                    fs::remove_file(&self.path).await?;
+                }
+
+                return Ok(());
+            },
+        };
+
+        log::info!("restoring file: {}", self.path);
+
+        let mut backup_path = PathBuf::from(dir);
+        backup_path.push("files");
+        backup_path.push(name);
+
+        fs::write(&self.path, fs::read(backup_path).await?).await?;
+
+        if let Some((uid, gid)) = self.owner {
+            chown(&self.path, uid, gid).await?;
+        }
+
+        if let Some(mode) = self.mode {
+            chmod(&self.path, mode).await?;
+        }
+
+        Ok(())
+    }
+}
+
+// runs recorded before multiple configs were supported have a single config
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Configs {
+    One(PathBuf),
+    Many(Vec<PathBuf>),
+}
+
+fn deserialize_configs<'de, D>(deserializer: D) -> std::result::Result<Vec<PathBuf>, D::Error>
+where D: Deserializer<'de> {
+    Ok(match Configs::deserialize(deserializer)? {
+        Configs::One(config) => vec![config],
+        Configs::Many(configs) => configs,
+    })
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Run {
+    pub id: String,
+    pub timestamp: String,
+    #[serde(alias = "config", deserialize_with = "deserialize_configs")]
+    pub configs: Vec<PathBuf>,
+    pub modules: Vec<String>,
+    pub changes: Vec<Record>,
+    #[serde(default)]
+    pub backups: Vec<Backup>,
+    #[serde(default)]
+    pub error: Option<String>,
+}
+
+impl Run {
+    pub fn new(configs: &[PathBuf]) -> Self {
+        let now = Local::now();
+        let mut id = now.format("%Y%m%d%H%M%S").to_string();
+        let mut n = 1;
+
+        while history_root().join(&id).exists() {
+            id = format!("{}-{}", now.format("%Y%m%d%H%M%S"), n);
+            n += 1;
+        }
+
+        Self {
+            id,
+            timestamp: now.to_rfc3339_opts(SecondsFormat::Secs, false),
+            configs: configs
+                .iter()
+                .map(|config| config.canonicalize().unwrap_or_else(|_| config.clone()))
+                .collect(),
+            modules: vec![],
+            changes: vec![],
+            backups: vec![],
+            error: None,
+        }
+    }
+
+    pub fn dir(&self) -> PathBuf {
+        let mut path = history_root();
+        path.push(&self.id);
+
+        path
+    }
+
+    pub async fn rollback(&self, on_restore: impl Fn(&str)) -> Result<()> {
+        let dir = self.dir();
+
+        for backup in self.backups.iter().rev() {
+            on_restore(&backup.path);
+            backup.restore(&dir).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Removes the packages which were installed by the run.
+    pub async fn remove_packages(&self, on_remove: impl Fn(&[String])) -> Result<()> {
+        let packages = self.changes
+            .iter()
+            .filter_map(|record| match &record.change {
+                Change::Packages { names } => Some(names.clone()),
+                _ => None,
+            })
+            .flatten()
+            .collect::<Vec<_>>();
+
+        if !packages.is_empty() {
+            on_remove(&packages);
+            actions::remove_packages(&packages).await?;
+        }
+
+        Ok(())
+    }
+
+    pub async fn save(&self) -> Result<()> {
+        let mut path = self.dir();
+        fs::create_dir_all(&path).await?;
+        path.push("run.json");
+
+        fs::write(path, serde_json::to_string_pretty(self)?).await?;
+
+        Ok(())
+    }
+}
+
+pub async fn load_run(id: &str) -> Result<Run> {
+    let mut path = history_root();
+    path.push(id);
+    path.push("run.json");
+
+    if !path.exists() {
+        return Err(Error::Custom(format!("unknown run {}", id)));
+    }
+
+    let contents = fs::read_to_string(&path).await?;
+
+    Ok(serde_json::from_str(&contents)?)
+}
+
+pub async fn list_runs() -> Result<Vec<Run>> {
+    let root = history_root();
+
+    if !root.exists() {
+        return Ok(vec![]);
+    }
+
+    let mut ids = vec![];
+    let mut entries = fs::read_dir(root).await?;
+
+    while let Some(entry) = entries.next_entry().await? {
+        ids.push(entry.file_name().to_string_lossy().to_string());
+    }
+
+    ids.sort();
+
+    let mut runs = vec![];
+
+    for id in ids {
+        match load_run(&id).await {
+            Ok(run) => runs.push(run),
+            Err(e) => log::warn!("skipping run {}: {}", id, e),
+        }
+    }
+
+    Ok(runs)
+}
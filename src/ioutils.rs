@@ -1,19 +1,64 @@
-use std::path::Path;
-use std::ffi::CString;
+use std::env;
+use std::path::{Path, PathBuf};
+use std::ffi::{CStr, CString};
 use std::fs::Permissions;
-use std::os::unix::prelude::PermissionsExt;
+use std::os::unix::prelude::{MetadataExt, PermissionsExt};
 
 use tokio::fs;
 use tokio::task::spawn_blocking;
 
 use super::result::{Result, Error};
 
+pub fn state_root() -> PathBuf {
+    PathBuf::from(
+        env::var("COMPUTE_STATE_ROOT")
+            .unwrap_or_else(|_| "/var/lib/compute".to_string()),
+    )
+}
+
 pub fn getuid() -> u32 {
     unsafe {
         libc::getuid()
     }
 }
 
+/// Reads the id of the user which is provisioned from `SUDO_UID` or `SUDO_GID`.
+pub fn sudo_id(name: &str) -> Result<u32> {
+    env::var(name)
+        .ok()
+        .and_then(|id| id.parse().ok())
+        .ok_or_else(|| Error::Custom(format!("{} should be set to the id of the user to provision", name)))
+}
+
+pub fn home_dir(uid: u32) -> Option<PathBuf> {
+    unsafe {
+        let passwd = libc::getpwuid(uid);
+
+        if passwd.is_null() || (*passwd).pw_dir.is_null() {
+            return None;
+        }
+
+        Some(PathBuf::from(
+            CStr::from_ptr((*passwd).pw_dir)
+                .to_string_lossy()
+                .to_string(),
+        ))
+    }
+}
+
+pub fn is_tty() -> bool {
+    unsafe {
+        libc::isatty(libc::STDOUT_FILENO) == 1
+    }
+}
+
+pub async fn owner_and_mode<P>(filename: P) -> Result<(u32, u32, u32)>
+where P: AsRef<Path> {
+    let metadata = fs::metadata(filename.as_ref()).await?;
+
+    Ok((metadata.uid(), metadata.gid(), metadata.mode() & 0o7777))
+}
+
 pub async fn chmod<P>(filename: P, mode: u32) -> Result<()>
 where P: AsRef<Path> {
     Ok(fs::set_permissions(
@@ -28,7 +73,7 @@ where S: AsRef<str> + Send {
         .unwrap();
     let result = spawn_blocking(move || {
         unsafe {
-            libc::chown(chown_filename.as_ptr() as *const i8, uid, gid)
+            libc::chown(chown_filename.as_ptr(), uid, gid)
         }
     }).await?;
 
@@ -0,0 +1,35 @@
+//! Compute as a library, to embed provisioning in other tools.
+//!
+//! A config is loaded with [`load_config`] and applied by a [`Provisioner`], set
+//! `dry_run` in its [`Options`] to plan instead. Register an [`Observer`] with
+//! [`Provisioner::subscribe`] to receive events while it runs. Like the `compute`
+//! binary, configs and modules read the user that is being provisioned from
+//! `SUDO_UID` and `SUDO_GID`, loading or applying a config fails if they aren't set.
+
+#![allow(clippy::result_large_err)]
+
+pub mod result;
+pub mod config;
+pub mod changes;
+pub mod history;
+pub mod facts;
+pub mod report;
+pub mod events;
+pub mod reporters;
+pub mod provisioner;
+
+pub(crate) mod ioutils;
+pub(crate) mod actions;
+pub(crate) mod modules;
+
+mod lockfile;
+mod plugins;
+mod staging;
+mod resources;
+
+pub use result::{Result, Error};
+pub use config::Config;
+pub use config::loader::{load_vars, load_config, ConfigContext};
+pub use events::{Event, Observer, Stage};
+pub use provisioner::{Provisioner, Options};
+pub use report::{Report, Outcome, Status, ResourceType};
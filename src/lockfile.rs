@@ -0,0 +1,68 @@
+use std::path::Path;
+use std::collections::BTreeMap;
+
+use tokio::fs;
+use serde::{Serialize, Deserialize};
+
+use super::result::Result;
+use super::config::sources::ModuleSource;
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LockedModule {
+    pub git: String,
+    #[serde(default, rename = "ref")]
+    pub reference: Option<String>,
+    pub commit: String,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Lockfile {
+    pub modules: BTreeMap<String, LockedModule>,
+    #[serde(skip)]
+    changed: bool,
+}
+
+impl Lockfile {
+    pub async fn load(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        let contents = fs::read_to_string(path).await?;
+
+        Ok(serde_json::from_str(&contents)?)
+    }
+
+    pub async fn save(&self, path: &Path) -> Result<()> {
+        if !self.changed {
+            return Ok(());
+        }
+
+        log::info!("writing lockfile: {:?}", path);
+
+        fs::write(path, serde_json::to_string_pretty(self)? + "\n").await?;
+
+        Ok(())
+    }
+
+    pub fn locked_commit(&self, name: &str, source: &ModuleSource) -> Option<&str> {
+        self.modules
+            .get(name)
+            .filter(|locked| locked.git == source.git && locked.reference == source.reference)
+            .map(|locked| locked.commit.as_str())
+    }
+
+    pub fn lock(&mut self, name: &str, source: &ModuleSource, commit: &str) {
+        if self.locked_commit(name, source) == Some(commit) {
+            return;
+        }
+
+        self.changed = true;
+        self.modules.insert(name.to_string(), LockedModule {
+            git: source.git.clone(),
+            reference: source.reference.clone(),
+            commit: commit.to_string(),
+        });
+    }
+}
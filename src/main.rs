@@ -1,20 +1,213 @@
-use std::fs;
+#![allow(clippy::result_large_err)]
+
 use std::env;
+use std::fs::File;
+use std::io::{self, Write};
+use std::os::unix::io::FromRawFd;
+use std::time::Instant;
+use std::path::PathBuf;
 use std::process::exit;
 
 use clap::derive::Clap;
 
+use compute::{facts, history, result};
+use compute::{Config, Provisioner, Options, load_vars, load_config, ConfigContext};
+use compute::history::Run;
+use compute::changes::Change;
+use compute::report::{Output, Report, Status};
+use compute::reporters::{TextReporter, ProgressReporter, JsonLinesReporter, QuietReporter};
+
 mod opts;
-mod result;
-mod config;
-mod ioutils;
-mod actions;
-mod modules;
-mod provisioner;
 
-use config::Config;
-use opts::{Opts, Cmd};
-use provisioner::Provisioner;
+use opts::{Opts, Cmd, ApplyOpts, HistoryOpts, RollbackOpts};
+
+fn split_list(values: &[String]) -> Vec<String> {
+    values
+        .iter()
+        .flat_map(|value| value.split(','))
+        .map(|value| value.trim().to_string())
+        .filter(|value| !value.is_empty())
+        .collect()
+}
+
+fn is_root() -> bool {
+    unsafe {
+        libc::getuid() == 0
+    }
+}
+
+fn redirect_stdout() -> File {
+    let _ = io::stdout().flush();
+
+    unsafe {
+        let stdout = libc::dup(libc::STDOUT_FILENO);
+
+        libc::dup2(libc::STDERR_FILENO, libc::STDOUT_FILENO);
+
+        File::from_raw_fd(stdout)
+    }
+}
+
+fn prepare(cmd: &str, opts: &ApplyOpts) -> (PathBuf, Config, Options) {
+    if !is_root() {
+        eprintln!("compute {} requires root privileges", cmd);
+        exit(1);
+    }
+
+    if let Some(uid) = opts.uid {
+        env::set_var("SUDO_UID", uid.to_string());
+    } else if env::var("SUDO_UID").is_err() {
+        eprintln!("either run compute {} with sudo or set the uid manually", cmd);
+        exit(1);
+    }
+
+    if let Some(gid) = opts.gid {
+        env::set_var("SUDO_GID", gid.to_string());
+    } else if env::var("SUDO_GID").is_err() {
+        eprintln!("either run compute {} with sudo or set the gid manually", cmd);
+        exit(1);
+    }
+
+    if let Output::Text | Output::Json = opts.output {
+        println!(">> reading config");
+    }
+
+    let facts = facts::gather();
+    let (config, vars) = match load_vars(&opts.var_files, &opts.vars)
+        .and_then(|vars| Ok((
+            load_config(&opts.filename, &opts.profile, &ConfigContext {
+                vars: &vars,
+                facts: &facts,
+            })?,
+            vars,
+        ))) {
+        Ok(result) => result,
+        Err(e) => {
+            eprintln!("failed to read config:\n  {}", e);
+            exit(1);
+        },
+    };
+    let mut path = opts.filename[0].clone();
+    path.pop();
+
+    let defaults = Options {
+        skip_downloads: opts.skip_downloads,
+        diff: opts.diff,
+        lockfile: Some(path.join("compute.lock")),
+        facts,
+        vars,
+        tags: split_list(&opts.tags),
+        skip_tags: split_list(&opts.skip_tags),
+        only: split_list(&opts.only),
+        jobs: opts.jobs,
+        ..Options::default()
+    };
+
+    (path, config, defaults)
+}
+
+fn config_name(opts: &ApplyOpts) -> String {
+    opts.filename[0]
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_else(|| "config".to_string())
+}
+
+fn redirect_output(opts: &ApplyOpts) -> Option<File> {
+    match opts.output {
+        Output::Json | Output::JsonLines => Some(redirect_stdout()),
+        _ => None,
+    }
+}
+
+fn subscribe(provisioner: &mut Provisioner, output: Output, stdout: &mut Option<File>) {
+    match output {
+        Output::Text | Output::Json => provisioner.subscribe(TextReporter::default()),
+        Output::Compact => provisioner.subscribe(ProgressReporter::new()),
+        Output::Quiet => provisioner.subscribe(QuietReporter),
+        Output::JsonLines => if let Some(stdout) = stdout.take() {
+            provisioner.subscribe(JsonLinesReporter::new(stdout));
+        },
+    }
+}
+
+async fn write_report(opts: &ApplyOpts, stdout: Option<File>, report: Report) {
+    if let Some(path) = &opts.report {
+        if let Err(e) = report.save(path).await {
+            eprintln!("failed to write report: {}", e);
+        }
+    }
+
+    if let Some(mut stdout) = stdout {
+        let json = serde_json::to_string_pretty(&report).unwrap();
+
+        if let Err(e) = writeln!(stdout, "{}", json) {
+            eprintln!("failed to write report: {}", e);
+        }
+    }
+}
+
+fn display_paths(paths: &[PathBuf]) -> String {
+    paths
+        .iter()
+        .map(|path| path.to_string_lossy())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+async fn show_history(opts: HistoryOpts) -> result::Result<()> {
+    if let Some(id) = opts.id {
+        let run = history::load_run(&id).await?;
+
+        println!("run {}", run.id);
+        println!("  timestamp: {}", run.timestamp);
+        println!("  configs: {}", display_paths(&run.configs));
+        println!("  modules: {}", run.modules.join(", "));
+
+        if let Some(error) = run.error {
+            println!("  error: {}", error);
+        }
+
+        println!("  changes:");
+
+        for record in run.changes {
+            match record.change {
+                Change::File { checksum: Some(checksum), .. } =>
+                    println!("    [{}] {} (previous checksum: {:08x})", record.module, record.change, checksum),
+                _ => println!("    [{}] {}", record.module, record.change),
+            }
+        }
+
+        return Ok(());
+    }
+
+    for run in history::list_runs().await? {
+        println!(
+            "{}  {}  {}  {} change(s){}",
+            run.id,
+            run.timestamp,
+            display_paths(&run.configs),
+            run.changes.len(),
+            if run.error.is_some() { "  (failed)" } else { "" },
+        );
+    }
+
+    Ok(())
+}
+
+async fn rollback(opts: RollbackOpts) -> result::Result<()> {
+    let run = history::load_run(&opts.id).await?;
+
+    println!(">> rolling back run {}", run.id);
+
+    run.rollback(|path| println!("restoring file: {}", path)).await?;
+
+    if opts.remove_packages {
+        run.remove_packages(|packages| println!("removing packages: {}", packages.join(", "))).await?;
+    }
+
+    Ok(())
+}
 
 #[tokio::main]
 async fn main() {
@@ -24,48 +217,141 @@ async fn main() {
 
     match opts.cmd {
         Cmd::Apply(opts) => {
-            if ioutils::getuid() != 0 {
-                eprintln!("compute apply requires root privileges");
-                exit(1);
+            let mut stdout = redirect_output(&opts);
+            let started = Instant::now();
+            let (path, config, defaults) = prepare("apply", &opts);
+            let mut run = Run::new(&opts.filename);
+
+            let mut provisioner = Provisioner::new(Options {
+                backup_dir: Some(run.dir()),
+                atomic: opts.atomic,
+                ..defaults
+            });
+
+            subscribe(&mut provisioner, opts.output, &mut stdout);
+
+            let result = provisioner.run(&config_name(&opts), &path, &config).await;
+
+            match &result {
+                Ok(_) => log::debug!("task finished succesfully"),
+                Err(e) => log::debug!("task errored: {}", e),
+            }
+
+            run.modules = provisioner.modules();
+            run.changes = provisioner.changes();
+            run.backups = provisioner.backups();
+            run.error = result.err().map(|e| e.to_string());
+
+            if let Err(e) = run.save().await {
+                eprintln!("failed to save history: {}", e);
             }
 
-            if let Some(uid) = opts.uid {
-                env::set_var("SUDO_UID", uid.to_string());
-            } else if env::var("SUDO_UID").is_err() {
-                eprintln!("either run compute apply with sudo or set the uid manually");
+            write_report(&opts, stdout, Report {
+                config: config_name(&opts),
+                run: Some(run.id.clone()),
+                dry_run: false,
+                success: run.error.is_none(),
+                error: run.error.clone(),
+                duration: started.elapsed().as_secs_f64(),
+                resources: provisioner.outcomes(),
+            }).await;
+
+            if run.error.is_some() {
                 exit(1);
             }
+        },
+        Cmd::Plan(opts) => {
+            let mut stdout = redirect_output(&opts);
+            let started = Instant::now();
+            let (path, config, defaults) = prepare("plan", &opts);
 
-            if let Some(gid) = opts.gid {
-                env::set_var("SUDO_GID", gid.to_string());
-            } else if env::var("SUDO_GID").is_err() {
-                eprintln!("either run compute apply with sudo or set the gid manually");
+            let mut provisioner = Provisioner::new(Options {
+                dry_run: true,
+                ..defaults
+            });
+
+            subscribe(&mut provisioner, opts.output, &mut stdout);
+
+            let result = provisioner.run(&config_name(&opts), &path, &config).await;
+
+            write_report(&opts, stdout, Report {
+                config: config_name(&opts),
+                run: None,
+                dry_run: true,
+                success: result.is_ok(),
+                error: result.as_ref().err().map(|e| e.to_string()),
+                duration: started.elapsed().as_secs_f64(),
+                resources: provisioner.outcomes(),
+            }).await;
+
+            if let Err(e) = result {
+                log::debug!("task errored: {}", e);
                 exit(1);
             }
 
-            println!(">> reading config");
+            if let Output::Quiet | Output::JsonLines = opts.output {
+                return;
+            }
+
+            let changes = provisioner.changes();
+            let skipped = provisioner.outcomes()
+                .into_iter()
+                .filter(|outcome| outcome.status == Status::Skipped)
+                .collect::<Vec<_>>();
 
-            let contents = fs::read_to_string(&opts.filename)
-                .expect("failed to read config");
-            let config: Config = json5::from_str(&contents)
-                .expect("failed to parse config");
-            let mut path = opts.filename;
-            path.pop();
+            if !skipped.is_empty() {
+                println!(">> skipped");
 
-            let provisioner = Provisioner::new(opts.skip_downloads);
+                for outcome in skipped {
+                    println!("  [{}] {} {}", outcome.module, outcome.kind, outcome.id);
+                }
+            }
 
-            println!(">> provisioning");
+            if changes.is_empty() {
+                println!(">> no changes found");
+                return;
+            }
 
-            match provisioner.run(&path, &config).await {
-                Ok(_) => {
-                    log::debug!("task finished succesfully");
-                    println!(">> finished");
-                },
+            println!(">> pending changes");
+
+            let mut current = None;
+
+            for record in changes {
+                if current.as_ref() != Some(&record.module) {
+                    println!(">>> {}", record.module);
+                    current = Some(record.module);
+                }
+
+                println!("  {}", record.change);
+            }
+        },
+        Cmd::Rollback(opts) => {
+            if !is_root() {
+                eprintln!("compute rollback requires root privileges");
+                exit(1);
+            }
+
+            if let Err(e) = rollback(opts).await {
+                eprintln!("rollback failed:\n  {}", e);
+                exit(1);
+            }
+
+            println!(">> finished");
+        },
+        Cmd::Facts(_) => {
+            match serde_json::to_string_pretty(&facts::gather()) {
+                Ok(json) => println!("{}", json),
                 Err(e) => {
-                    log::debug!("task errored: {}", e);
-                    println!("provisioning failed:\n  {}", e);
+                    eprintln!("failed to gather facts: {}", e);
+                    exit(1);
                 },
             }
         },
+        Cmd::History(opts) => {
+            if let Err(e) = show_history(opts).await {
+                eprintln!("failed to read history: {}", e);
+                exit(1);
+            }
+        },
     }
 }
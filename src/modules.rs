@@ -1,66 +1,410 @@
 use std::env;
-use std::path::PathBuf;
+use std::fmt;
+use std::result;
+use std::path::{Path, PathBuf};
 use std::collections::HashMap;
 
 use tokio::fs;
 use serde::Deserialize;
-use tera::{Tera, Map, Context, Value};
+use tera::{Tera, Map, Value};
 
 use super::config::Config;
+use super::ioutils::home_dir;
+use super::facts::template_context;
 use super::result::{Result, Error};
 
-pub async fn load_module(name: &str, vars: Value) -> Result<(PathBuf, Module)> {
-    let mut config_path = PathBuf::from(format!(
-        "{}/{}.json5",
+pub fn search_path(root_dir: &Path, config: &Config) -> Vec<PathBuf> {
+    let mut paths = vec![root_dir.join("modules")];
+
+    if let Some(home) = env::var("SUDO_UID")
+        .ok()
+        .and_then(|uid| uid.parse().ok())
+        .and_then(home_dir) {
+        paths.push(home.join(".config/compute/modules"));
+    }
+
+    paths.extend(config.module_paths
+        .iter()
+        .map(|path| root_dir.join(path)));
+    paths.push(PathBuf::from(
         env::var("COMPUTE_MODULES_ROOT")
-            .unwrap_or("/usr/share/compute/modules".to_string()),
-        name,
+            .unwrap_or_else(|_| "/usr/share/compute/modules".to_string()),
     ));
 
-    if !config_path.exists() {
-        return Err(Error::Custom(format!("failed to load module {}", name)));
+    paths
+}
+
+fn strip_tags(contents: &str) -> String {
+    let mut output = String::new();
+    let mut rest = contents;
+
+    while let Some(start) = ["{{", "{%", "{#"]
+        .iter()
+        .filter_map(|tag| rest.find(tag))
+        .min() {
+        output.push_str(&rest[..start]);
+
+        let end_tag = match &rest[start..start + 2] {
+            "{{" => "}}",
+            "{%" => "%}",
+            _ => "#}",
+        };
+
+        if end_tag == "}}" {
+            output.push_str("null");
+        }
+
+        rest = match rest[start..].find(end_tag) {
+            Some(end) => &rest[start + end + 2..],
+            None => "",
+        };
+    }
+
+    output.push_str(rest);
+    output
+}
+
+fn skip_string(bytes: &[u8], mut i: usize) -> usize {
+    let quote = bytes[i];
+
+    i += 1;
+
+    while i < bytes.len() && bytes[i] != quote {
+        if bytes[i] == b'\\' {
+            i += 1;
+        }
+
+        i += 1;
+    }
+
+    i
+}
+
+fn skip_whitespace(bytes: &[u8], mut i: usize) -> usize {
+    while i < bytes.len() && bytes[i].is_ascii_whitespace() {
+        i += 1;
+    }
+
+    i
+}
+
+/// Returns the value of the top-level `module` key. The rest of a module file is only valid
+/// json5 once it's rendered, so only this section is parsed to read the definition.
+fn module_section(contents: &str) -> Option<&str> {
+    let bytes = contents.as_bytes();
+    let mut depth = 0;
+    let mut start = None;
+    let mut i = 0;
+
+    while i < bytes.len() {
+        let key = match bytes[i] {
+            b'/' if bytes.get(i + 1) == Some(&b'/') => {
+                i = contents[i..].find('\n').map(|end| i + end).unwrap_or(bytes.len());
+                continue;
+            },
+            b'/' if bytes.get(i + 1) == Some(&b'*') => {
+                i = contents[i + 2..].find("*/").map(|end| i + end + 4).unwrap_or(bytes.len());
+                continue;
+            },
+            b'{' if bytes.get(i + 1) == Some(&b'#') => {
+                i = contents[i + 2..].find("#}").map(|end| i + end + 4).unwrap_or(bytes.len());
+                continue;
+            },
+            b'\'' | b'"' => {
+                let end = skip_string(bytes, i);
+                let key = contents.get(i + 1..end);
+
+                i = end + 1;
+                key
+            },
+            b'{' => {
+                depth += 1;
+                i += 1;
+                continue;
+            },
+            b'}' => {
+                depth -= 1;
+
+                if let (Some(start), 1) = (start, depth) {
+                    return Some(&contents[start..=i]);
+                }
+
+                i += 1;
+                continue;
+            },
+            c if c.is_ascii_alphabetic() || c == b'_' => {
+                let end = contents[i..]
+                    .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+                    .map(|end| i + end)
+                    .unwrap_or(bytes.len());
+                let key = contents.get(i..end);
+
+                i = end;
+                key
+            },
+            _ => {
+                i += 1;
+                continue;
+            },
+        };
+
+        if depth != 1 || start.is_some() || key != Some("module") {
+            continue;
+        }
+
+        let colon = skip_whitespace(bytes, i);
+
+        if bytes.get(colon) == Some(&b':') {
+            let value = skip_whitespace(bytes, colon + 1);
+
+            if bytes.get(value) == Some(&b'{') {
+                start = Some(value);
+                i = value;
+            }
+        }
     }
 
-    let mut env_vars = Map::new();
+    None
+}
+
+#[derive(Deserialize, Debug)]
+struct ModuleHeader {
+    module: ModuleConfig,
+}
+
+pub struct ModuleFile {
+    pub path: PathBuf,
+    pub definition: ModuleConfig,
+    name: String,
+    contents: String,
+}
+
+pub async fn read_module(search_path: &[PathBuf], name: &str) -> Result<ModuleFile> {
+    let path = match search_path
+        .iter()
+        .map(|dir| dir.join(format!("{}.json5", name)))
+        .find(|path| path.exists()) {
+        Some(path) => path,
+        None => return Err(Error::Custom(format!(
+            "failed to load module {}: not found in {}",
+            name,
+            search_path
+                .iter()
+                .map(|dir| dir.to_string_lossy())
+                .collect::<Vec<_>>()
+                .join(", "),
+        ))),
+    };
+
+    log::info!("reading module {} from: {:?}", name, path);
+
+    let contents = fs::read_to_string(&path).await?;
+
+    Ok(ModuleFile {
+        path,
+        definition: read_definition(name, &contents)?,
+        name: name.to_string(),
+        contents,
+    })
+}
+
+fn read_definition(name: &str, contents: &str) -> Result<ModuleConfig> {
+    let section = module_section(contents)
+        .ok_or_else(|| Error::Custom(format!("failed to read definition of module {}: missing module section", name)))?;
+    let header: ModuleHeader = json5::from_str(&format!("{{module: {}}}", strip_tags(section)))
+        .map_err(|e| Error::Custom(format!("failed to read definition of module {}: {}", name, e)))?;
+
+    Ok(header.module)
+}
+
+impl ModuleFile {
+    pub fn prepare_vars(&self, mut vars: Value) -> Result<Value> {
+        self.definition.apply_defaults(&mut vars);
+        self.definition.validate(&vars)?;
 
-    for (key, val) in env::vars() {
-        env_vars.insert(key, Value::String(val));
+        Ok(vars)
     }
 
-    let mut context = Context::new();
+    pub fn render(&self, vars: &Value, outputs: &Map<String, Value>, facts: &Map<String, Value>) -> Result<(PathBuf, Module)> {
+        let mut context = template_context(facts)?;
 
-    context.insert("vars", &vars);
-    context.insert("env", &Value::Object(env_vars));
-    context.insert("uid", &env::var("SUDO_UID").unwrap().parse::<i32>().unwrap());
-    context.insert("gid", &env::var("SUDO_GID").unwrap().parse::<i32>().unwrap());
+        context.insert("vars", vars);
+        context.insert("modules", outputs);
 
-    let contents = fs::read_to_string(&config_path).await?;
-    let contents = Tera::one_off(
-        &contents,
-        &context,
-        false,
-    ).map_err(|e| Error::from_template_err(format!("{}.json5", name), e))?;
+        let contents = Tera::one_off(
+            &self.contents,
+            &context,
+            false,
+        ).map_err(|e| Error::from_template_err(format!("{}.json5", self.name), e))?;
 
-    config_path.pop();
+        let mut root = self.path.clone();
+        root.pop();
 
-    let module: Module = json5::from_str(&contents)?;
+        let module: Module = json5::from_str(&contents)?;
 
-    module.validate(vars)?;
+        Ok((root, module))
+    }
+}
 
-    Ok((config_path, module))
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum VarType {
+    String,
+    Float,
+    Integer,
+    Boolean,
+    Array,
+    Object,
+}
+
+impl VarType {
+    fn matches(&self, value: &Value) -> bool {
+        match self {
+            Self::String => value.is_string(),
+            Self::Float => value.is_number(),
+            Self::Integer => value.is_i64() || value.is_u64(),
+            Self::Boolean => value.is_boolean(),
+            Self::Array => value.is_array(),
+            Self::Object => value.is_object(),
+        }
+    }
+}
+
+impl fmt::Display for VarType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", match self {
+            Self::String => "string",
+            Self::Float => "float",
+            Self::Integer => "integer",
+            Self::Boolean => "boolean",
+            Self::Array => "array",
+            Self::Object => "object",
+        })
+    }
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(n) if n.is_f64() => "float",
+        Value::Number(_) => "integer",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
 }
 
 #[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
 pub struct VarDef {
+    #[serde(default)]
     required: bool,
     #[serde(rename = "type")]
-    kind: String,
+    kind: VarType,
+    #[serde(default)]
+    items: Option<Box<VarDef>>,
+    #[serde(default)]
+    properties: Option<HashMap<String, VarDef>>,
+    #[serde(default, rename = "enum")]
+    allowed: Option<Vec<Value>>,
+    #[serde(default)]
+    default: Option<Value>,
+}
+
+impl VarDef {
+    fn check_definition(&self, path: &str) -> result::Result<(), String> {
+        if let Some(default) = &self.default {
+            if self.required {
+                return Err(format!("{} is required and can't have a default", path));
+            }
+
+            self.check(path, default)?;
+        }
+
+        if let Some(items) = &self.items {
+            items.check_definition(&format!("{}[]", path))?;
+        }
+
+        if let Some(properties) = &self.properties {
+            for (name, property) in properties {
+                property.check_definition(&format!("{}.{}", path, name))?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn apply_defaults(&self, value: &mut Value) {
+        match value {
+            Value::Array(values) => if let Some(items) = &self.items {
+                for item in values {
+                    items.apply_defaults(item);
+                }
+            },
+            Value::Object(values) => if let Some(properties) = &self.properties {
+                apply_defaults(properties, values);
+            },
+            _ => {},
+        }
+    }
+
+    fn check(&self, path: &str, value: &Value) -> result::Result<(), String> {
+        if !self.kind.matches(value) {
+            return Err(format!("{} should be of type {} but is {}", path, self.kind, type_name(value)));
+        }
+
+        if let Some(allowed) = &self.allowed {
+            if !allowed.contains(value) {
+                return Err(format!(
+                    "{} should be one of {} but is {}",
+                    path,
+                    allowed.iter()
+                        .map(|v| v.to_string())
+                        .collect::<Vec<_>>()
+                        .join(", "),
+                    value,
+                ));
+            }
+        }
+
+        if let (Some(items), Value::Array(values)) = (&self.items, value) {
+            for (i, item) in values.iter().enumerate() {
+                items.check(&format!("{}[{}]", path, i), item)?;
+            }
+        }
+
+        if let (Some(properties), Value::Object(values)) = (&self.properties, value) {
+            for (name, property) in properties {
+                match values.get(name) {
+                    Some(value) => property.check(&format!("{}.{}", path, name), value)?,
+                    None if property.required => return Err(format!("{}.{} is required", path, name)),
+                    None => continue,
+                }
+            }
+
+            for name in values.keys() {
+                if !properties.contains_key(name) {
+                    return Err(format!("{}.{} is unknown", path, name));
+                }
+            }
+        }
+
+        Ok(())
+    }
 }
 
 #[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
 pub struct ModuleConfig {
     pub name: String,
     pub vars: HashMap<String, VarDef>,
+    #[serde(default)]
+    pub depends_on: Vec<String>,
+    #[serde(default)]
+    pub outputs: Map<String, Value>,
+    #[serde(default)]
+    pub tags: Vec<String>,
 }
 
 #[derive(Deserialize, Debug)]
@@ -69,21 +413,48 @@ pub struct Module {
     pub config: Config,
 }
 
-impl Module {
-    fn validate(&self, vars: Value) -> Result<()> {
+fn apply_defaults(defs: &HashMap<String, VarDef>, vars: &mut Map<String, Value>) {
+    for (name, var) in defs {
+        match vars.get_mut(name) {
+            Some(value) => var.apply_defaults(value),
+            None => if let Some(default) = &var.default {
+                vars.insert(name.clone(), default.clone());
+            },
+        }
+    }
+}
+
+impl ModuleConfig {
+    fn apply_defaults(&self, vars: &mut Value) {
         if let Value::Object(vars) = vars {
-            for (name, var) in &self.module.vars {
-                if var.required && !vars.contains_key(name) {
-                    return Err(Error::Custom(
-                        format!("missing required var {} for module {}", name, self.module.name),
-                    ));
+            apply_defaults(&self.vars, vars);
+        }
+    }
+
+    fn validate(&self, vars: &Value) -> Result<()> {
+        for (name, var) in &self.vars {
+            var.check_definition(name).map_err(|e| Error::Custom(
+                format!("invalid definition of var for module {}: {}", self.name, e),
+            ))?;
+        }
+
+        if let Value::Object(vars) = vars {
+            for (name, var) in &self.vars {
+                match vars.get(name) {
+                    Some(value) => var.check(name, value).map_err(|e| Error::Custom(
+                        format!("invalid var for module {}: {}", self.name, e),
+                    ))?,
+                    None if var.required => return Err(Error::Custom(
+                        format!("missing required var {} for module {}", name, self.name),
+                    )),
+                    None => continue,
                 }
             }
 
             for name in vars.keys() {
-                if !self.module.vars.contains_key(name) {
+                if !self.vars.contains_key(name) {
                     return Err(Error::Custom(
-                        format!("unknown var {} for module {}", name, self.module.name),
+                        format!("unknown var {} for module {}", name, self.name),
                     ));
                 }
             }
@@ -92,7 +463,333 @@ impl Module {
         }
 
         Err(Error::Custom(
-            format!("invalid type for vars of module {}", self.module.name),
+            format!("invalid type for vars of module {}", self.name),
         ))
     }
 }
+
+pub struct LoadedModule {
+    pub name: String,
+    pub source: String,
+    pub file: ModuleFile,
+    pub vars: Value,
+}
+
+fn visit(
+    index: usize,
+    modules: &[LoadedModule],
+    visited: &mut Vec<bool>,
+    path: &mut Vec<usize>,
+    order: &mut Vec<usize>,
+) -> Result<()> {
+    if visited[index] {
+        return Ok(());
+    }
+
+    if let Some(pos) = path.iter().position(|i| *i == index) {
+        let cycle = path[pos..]
+            .iter()
+            .chain(Some(&index))
+            .map(|i| modules[*i].name.as_str())
+            .collect::<Vec<_>>();
+
+        return Err(Error::Custom(
+            format!("dependency cycle between modules: {}", cycle.join(" -> ")),
+        ));
+    }
+
+    path.push(index);
+
+    let loaded = &modules[index];
+
+    for dependency in &loaded.file.definition.depends_on {
+        let mut dependencies = (0..modules.len())
+            .filter(|i| &modules[*i].name == dependency)
+            .collect::<Vec<_>>();
+
+        if dependencies.is_empty() {
+            dependencies = (0..modules.len())
+                .filter(|i| &modules[*i].source == dependency)
+                .collect();
+        }
+
+        // dependencies only order the modules that are configured, they don't pull in new ones
+        if dependencies.is_empty() {
+            log::debug!("module {} depends on {} which is not configured", loaded.name, dependency);
+        }
+
+        for dependency_index in dependencies {
+            visit(dependency_index, modules, visited, path, order)?;
+        }
+    }
+
+    path.pop();
+    visited[index] = true;
+    order.push(index);
+
+    Ok(())
+}
+
+pub fn sort_modules(modules: Vec<LoadedModule>) -> Result<Vec<LoadedModule>> {
+    let mut visited = vec![false; modules.len()];
+    let mut order = vec![];
+
+    for index in 0..modules.len() {
+        visit(index, &modules, &mut visited, &mut vec![], &mut order)?;
+    }
+
+    let mut modules = modules
+        .into_iter()
+        .map(Some)
+        .collect::<Vec<_>>();
+
+    Ok(order
+        .into_iter()
+        .map(|index| modules[index].take().unwrap())
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+    use std::collections::HashMap;
+
+    use tera::{Map, Value};
+
+    use super::{module_section, read_definition, strip_tags, sort_modules};
+    use super::{Error, LoadedModule, ModuleConfig, ModuleFile, VarDef};
+
+    fn loaded(name: &str, source: &str, depends_on: &[&str]) -> LoadedModule {
+        LoadedModule {
+            name: name.to_string(),
+            source: source.to_string(),
+            file: ModuleFile {
+                path: PathBuf::new(),
+                definition: ModuleConfig {
+                    name: source.to_string(),
+                    vars: HashMap::new(),
+                    depends_on: depends_on.iter().map(|name| name.to_string()).collect(),
+                    outputs: Map::new(),
+                    tags: vec![],
+                },
+                name: source.to_string(),
+                contents: String::new(),
+            },
+            vars: Value::Null,
+        }
+    }
+
+    fn order(modules: Vec<LoadedModule>) -> Vec<String> {
+        sort_modules(modules)
+            .unwrap()
+            .into_iter()
+            .map(|module| module.name)
+            .collect()
+    }
+
+    fn var(definition: &str) -> VarDef {
+        json5::from_str(definition).unwrap()
+    }
+
+    fn value(value: &str) -> Value {
+        json5::from_str(value).unwrap()
+    }
+
+    #[test]
+    fn module_section_ignores_braces_in_strings() {
+        let contents = "{ title: '}{', module: { name: 'a}', vars: { x: { type: \"string\", default: '{' } } } }";
+
+        assert_eq!(
+            module_section(contents),
+            Some("{ name: 'a}', vars: { x: { type: \"string\", default: '{' } } }"),
+        );
+    }
+
+    #[test]
+    fn module_section_skips_tags_before_root() {
+        let contents = "{% set home = env.HOME ~ '/.cargo' %}\n{# it's the root #}\n{\n  module: { name: 'a', vars: {} },\n  config: {},\n}";
+
+        assert_eq!(module_section(contents), Some("{ name: 'a', vars: {} }"));
+    }
+
+    #[test]
+    fn module_section_skips_quotes_in_comments() {
+        let contents = "{\n  // don't read this\n  /* or \"this\" */\n  module: { name: 'a', vars: {} },\n}";
+
+        assert_eq!(module_section(contents), Some("{ name: 'a', vars: {} }"));
+    }
+
+    #[test]
+    fn module_section_only_reads_top_level_key() {
+        let contents = "{ config: { module: { name: 'nested' } }, 'module': { name: 'a', vars: {} } }";
+
+        assert_eq!(module_section(contents), Some("{ name: 'a', vars: {} }"));
+        assert_eq!(module_section("{ config: { module: { name: 'nested' } } }"), None);
+    }
+
+    #[test]
+    fn strip_tags_replaces_expressions() {
+        assert_eq!(strip_tags("a: {{ vars.a }}, {% if x %}b: 1{% endif %}{# c #}"), "a: null, b: 1");
+    }
+
+    #[test]
+    fn read_definition_with_tags_in_outputs() {
+        let contents = "{\n  module: {\n    name: 'a',\n    vars: {},\n    outputs: {\n      home: '{{ env.HOME }}/a',\n      port: {{ vars.port | default(value=80) }},\n      {% if vars.x %}x: true,{% endif %}\n    },\n  },\n  config: { scripts: [{{ vars.script }}] },\n}";
+        let definition = read_definition("a", contents).unwrap();
+
+        assert_eq!(definition.name, "a");
+        // outputs are only rendered with the rest of the module, the header just has to parse
+        assert!(definition.outputs.contains_key("home"));
+        assert!(definition.outputs["port"].is_null());
+    }
+
+    #[test]
+    fn read_definition_of_bundled_rust_module() {
+        let definition = read_definition("rust", include_str!("../modules/rust.json5")).unwrap();
+
+        assert_eq!(definition.name, "rust");
+        assert!(definition.vars["version"].required);
+        assert!(definition.vars.contains_key("components"));
+        assert!(definition.outputs.contains_key("cargoHome"));
+    }
+
+    #[test]
+    fn check_matches_types() {
+        assert!(var("{ type: 'string' }").check("version", &value("'1.20'")).is_ok());
+        assert!(var("{ type: 'float' }").check("ratio", &value("1")).is_ok());
+        assert!(var("{ type: 'integer' }").check("count", &value("3")).is_ok());
+        assert_eq!(
+            var("{ type: 'string' }").check("version", &value("1.2")),
+            Err("version should be of type string but is float".to_string()),
+        );
+        assert_eq!(
+            var("{ type: 'integer' }").check("count", &value("1.5")),
+            Err("count should be of type integer but is float".to_string()),
+        );
+    }
+
+    #[test]
+    fn check_enum() {
+        let def = var("{ type: 'string', enum: ['dhcp', 'static'] }");
+
+        assert!(def.check("mode", &value("'dhcp'")).is_ok());
+        assert_eq!(
+            def.check("mode", &value("'manual'")),
+            Err("mode should be one of \"dhcp\", \"static\" but is \"manual\"".to_string()),
+        );
+    }
+
+    #[test]
+    fn check_nested_values() {
+        let def = var("{ type: 'array', items: { type: 'object', properties: { name: { type: 'string', required: true }, url: { type: 'string' } } } }");
+
+        assert!(def.check("apps", &value("[{ name: 'a' }, { name: 'b', url: 'c' }]")).is_ok());
+        assert_eq!(
+            def.check("apps", &value("[{ name: 'a' }, { url: 'c' }]")),
+            Err("apps[1].name is required".to_string()),
+        );
+        assert_eq!(
+            def.check("apps", &value("[{ name: 1 }]")),
+            Err("apps[0].name should be of type string but is integer".to_string()),
+        );
+        assert_eq!(
+            def.check("apps", &value("[{ name: 'a', version: '1' }]")),
+            Err("apps[0].version is unknown".to_string()),
+        );
+    }
+
+    #[test]
+    fn check_definition_of_defaults() {
+        assert!(var("{ type: 'array', default: [], items: { type: 'string' } }").check_definition("components").is_ok());
+        assert_eq!(
+            var("{ type: 'string', default: 1 }").check_definition("version"),
+            Err("version should be of type string but is integer".to_string()),
+        );
+        assert_eq!(
+            var("{ type: 'string', required: true, default: 'a' }").check_definition("version"),
+            Err("version is required and can't have a default".to_string()),
+        );
+    }
+
+    #[test]
+    fn apply_defaults_fills_missing_vars() {
+        let definition: ModuleConfig = json5::from_str("{
+            name: 'a',
+            vars: {
+                version: { type: 'string', required: true },
+                components: { type: 'array', default: ['rustfmt'] },
+                apps: {
+                    type: 'array',
+                    items: { type: 'object', properties: { name: { type: 'string' }, channel: { type: 'string', default: 'stable' } } },
+                },
+            },
+        }").unwrap();
+        let mut vars = value("{ version: '1.50', apps: [{ name: 'a' }, { name: 'b', channel: 'beta' }] }");
+
+        definition.apply_defaults(&mut vars);
+
+        assert_eq!(vars, value("{
+            version: '1.50',
+            components: ['rustfmt'],
+            apps: [{ name: 'a', channel: 'stable' }, { name: 'b', channel: 'beta' }],
+        }"));
+        assert!(definition.validate(&vars).is_ok());
+    }
+
+    #[test]
+    fn apply_defaults_keeps_given_vars() {
+        let definition: ModuleConfig = json5::from_str("{ name: 'a', vars: { components: { type: 'array', default: ['rustfmt'] } } }").unwrap();
+        let mut vars = value("{ components: [] }");
+
+        definition.apply_defaults(&mut vars);
+
+        assert_eq!(vars, value("{ components: [] }"));
+    }
+
+    #[test]
+    fn sort_modules_configures_dependencies_first() {
+        assert_eq!(
+            order(vec![
+                loaded("golang", "golang", &["git"]),
+                loaded("networking", "networking", &[]),
+                loaded("git", "git", &[]),
+            ]),
+            vec!["git", "golang", "networking"],
+        );
+    }
+
+    #[test]
+    fn sort_modules_matches_instances_by_source() {
+        assert_eq!(
+            order(vec![
+                loaded("golang", "golang", &["git"]),
+                loaded("work-git", "git", &[]),
+                loaded("home-git", "git", &[]),
+            ]),
+            vec!["work-git", "home-git", "golang"],
+        );
+    }
+
+    #[test]
+    fn sort_modules_ignores_unconfigured_dependencies() {
+        assert_eq!(
+            order(vec![loaded("golang", "golang", &["git"]), loaded("rust", "rust", &[])]),
+            vec!["golang", "rust"],
+        );
+    }
+
+    #[test]
+    fn sort_modules_detects_cycles() {
+        let result = sort_modules(vec![
+            loaded("rust", "rust", &[]),
+            loaded("a", "a", &["b"]),
+            loaded("b", "b", &["c"]),
+            loaded("c", "c", &["a"]),
+        ]);
+
+        match result {
+            Err(Error::Custom(message)) => assert_eq!(message, "dependency cycle between modules: a -> b -> c -> a"),
+            _ => panic!("expected a dependency cycle"),
+        }
+    }
+}
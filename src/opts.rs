@@ -2,6 +2,8 @@ use std::path::PathBuf;
 
 use clap::Clap;
 
+use compute::report::Output;
+
 #[derive(Debug, Clap)]
 #[clap(version = "0.1", author = "Dillen <info@dillen.dev>")]
 pub struct Opts {
@@ -11,18 +13,63 @@ pub struct Opts {
 
 #[derive(Debug, Clap)]
 pub struct ApplyOpts {
-    #[clap(short)]
-    pub filename: PathBuf,
+    #[clap(short, required = true, number_of_values = 1)]
+    pub filename: Vec<PathBuf>,
     #[clap(short)]
     pub uid: Option<u32>,
     #[clap(short)]
     pub gid: Option<u32>,
     #[clap(long)]
     pub skip_downloads: bool,
+    #[clap(long, number_of_values = 1)]
+    pub profile: Vec<String>,
+    #[clap(long = "var", number_of_values = 1)]
+    pub vars: Vec<String>,
+    #[clap(long = "var-file", number_of_values = 1)]
+    pub var_files: Vec<PathBuf>,
+    #[clap(long, number_of_values = 1)]
+    pub tags: Vec<String>,
+    #[clap(long, number_of_values = 1)]
+    pub skip_tags: Vec<String>,
+    #[clap(long, number_of_values = 1)]
+    pub only: Vec<String>,
+    #[clap(long)]
+    pub diff: bool,
+    #[clap(long)]
+    pub atomic: bool,
+    #[clap(long, default_value = "text")]
+    pub output: Output,
+    #[clap(long)]
+    pub report: Option<PathBuf>,
+    #[clap(short, long, default_value = "4")]
+    pub jobs: usize,
+}
+
+#[derive(Debug, Clap)]
+pub struct HistoryOpts {
+    pub id: Option<String>,
+}
+
+#[derive(Debug, Clap)]
+pub struct RollbackOpts {
+    pub id: String,
+    #[clap(long)]
+    pub remove_packages: bool,
 }
 
+#[derive(Debug, Clap)]
+pub struct FactsOpts {}
+
 #[derive(Debug, Clap)]
 pub enum Cmd {
     #[clap()]
     Apply(ApplyOpts),
+    #[clap()]
+    Plan(ApplyOpts),
+    #[clap()]
+    History(HistoryOpts),
+    #[clap()]
+    Rollback(RollbackOpts),
+    #[clap()]
+    Facts(FactsOpts),
 }
@@ -0,0 +1,51 @@
+use std::env;
+use std::path::{Path, PathBuf};
+
+use super::config::Config;
+use super::result::{Result, Error};
+
+/// Returns the directories in which plugins are looked up for a config, those of a module
+/// config are followed by the search path of the config which uses the module.
+pub fn search_path(root_dir: &Path, config: &Config, parent: Option<&[PathBuf]>) -> Vec<PathBuf> {
+    let mut paths = vec![root_dir.join("plugins")];
+
+    paths.extend(config.plugin_paths
+        .iter()
+        .map(|path| root_dir.join(path)));
+
+    if let Some(parent) = parent {
+        paths.extend(parent.iter().cloned());
+        return paths;
+    }
+
+    paths.push(PathBuf::from(
+        env::var("COMPUTE_PLUGINS_ROOT")
+            .unwrap_or_else(|_| "/usr/share/compute/plugins".to_string()),
+    ));
+
+    if let Some(path) = env::var_os("PATH") {
+        paths.extend(env::split_paths(&path));
+    }
+
+    paths
+}
+
+pub fn find_plugin(search_path: &[PathBuf], kind: &str) -> Result<PathBuf> {
+    if kind.is_empty() || !kind.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+        return Err(Error::Custom(format!("invalid resource type {}", kind)));
+    }
+
+    let name = format!("compute-resource-{}", kind);
+
+    match search_path
+        .iter()
+        .map(|dir| dir.join(&name))
+        .find(|path| path.is_file()) {
+        Some(path) => {
+            log::info!("using plugin {} for resource type: {}", path.to_string_lossy(), kind);
+
+            Ok(path)
+        },
+        None => Err(Error::Custom(format!("no plugin found for resource type {} ({})", kind, name))),
+    }
+}
@@ -1,112 +1,658 @@
-use std::str;
+use std::sync::Mutex;
+use std::future::Future;
+use std::time::{Duration, Instant};
 use std::path::{PathBuf, Path};
 
+use tera::{Tera, Map, Value};
+use futures::stream::{self, StreamExt};
 use async_recursion::async_recursion;
 
 use super::actions;
-use super::result::Result;
-use super::modules::load_module;
-use super::ioutils::{chmod, chown};
-use super::config::{Config, files::TemplateSource};
+use super::changes::{Change, Record};
+use super::result::{Result, Error};
+use super::history::Backup;
+use super::facts::template_context;
+use super::report::{Outcome, ResourceType, Status};
+use super::events::{Event, Observer, Stage};
+use super::lockfile::Lockfile;
+use super::staging::Staging;
+use super::modules::{read_module, sort_modules, search_path, LoadedModule};
+use super::plugins;
+use super::resources::{
+    self,
+    Resource,
+    RepositoryResource,
+    PackagesResource,
+    ScriptResource,
+    FileResource,
+    PluginResource,
+};
+use super::config::{
+    Config,
+    handlers::Handler,
+    sources::ModuleSource,
+};
+
+#[derive(Default)]
+pub struct Options {
+    pub skip_downloads: bool,
+    pub dry_run: bool,
+    pub diff: bool,
+    pub backup_dir: Option<PathBuf>,
+    pub atomic: bool,
+    pub lockfile: Option<PathBuf>,
+    pub facts: Map<String, Value>,
+    pub vars: Map<String, Value>,
+    pub tags: Vec<String>,
+    pub skip_tags: Vec<String>,
+    pub only: Vec<String>,
+    pub jobs: usize,
+}
+
+struct Scope {
+    vars: Value,
+    tags: Vec<String>,
+    selected: bool,
+    plugin_path: Vec<PathBuf>,
+}
+
+async fn timed<T>(future: impl Future<Output = T>) -> (Duration, T) {
+    let started = Instant::now();
+    let output = future.await;
+
+    (started.elapsed(), output)
+}
 
 pub struct Provisioner {
-    skip_downloads: bool,
+    opts: Options,
+    modules: Mutex<Vec<String>>,
+    changes: Mutex<Vec<Record>>,
+    outcomes: Mutex<Vec<Outcome>>,
+    backups: Mutex<Vec<Backup>>,
+    backed_up: Mutex<Vec<String>>,
+    staging: Option<Staging>,
+    handlers: Mutex<Vec<(String, Handler)>>,
+    notified: Mutex<Vec<String>>,
+    lockfile: Mutex<Lockfile>,
+    outputs: Mutex<Map<String, Value>>,
+    observers: Vec<Box<dyn Observer>>,
 }
 
 impl Provisioner {
-    pub fn new(skip_downloads: bool) -> Self {
+    pub fn new(opts: Options) -> Self {
+        let staging = match (opts.atomic, &opts.backup_dir) {
+            (true, Some(dir)) => Some(Staging::new(dir.join("staging"))),
+            _ => None,
+        };
+
         Self {
-            skip_downloads: skip_downloads,
+            opts,
+            modules: Mutex::new(vec![]),
+            changes: Mutex::new(vec![]),
+            outcomes: Mutex::new(vec![]),
+            backups: Mutex::new(vec![]),
+            backed_up: Mutex::new(vec![]),
+            staging,
+            handlers: Mutex::new(vec![]),
+            notified: Mutex::new(vec![]),
+            lockfile: Mutex::new(Lockfile::default()),
+            outputs: Mutex::new(Map::new()),
+            observers: vec![],
         }
     }
 
-    async fn configure_apt(&self, _root_dir: &Path, config: &Config) -> Result<()> {
-        for repo in &config.apt.repositories {
-            actions::add_repository(repo).await?;
+    /// Registers an observer which receives every event of the runs of this provisioner
+    pub fn subscribe(&mut self, observer: impl Observer + 'static) {
+        self.observers.push(Box::new(observer));
+    }
+
+    fn emit(&self, event: Event) {
+        for observer in &self.observers {
+            observer.on_event(&event);
         }
+    }
 
-        actions::install_packages(&config.apt.packages).await?;
+    pub fn modules(&self) -> Vec<String> {
+        self.modules.lock()
+            .unwrap()
+            .clone()
+    }
+
+    pub fn changes(&self) -> Vec<Record> {
+        self.changes.lock()
+            .unwrap()
+            .clone()
+    }
+
+    pub fn outcomes(&self) -> Vec<Outcome> {
+        self.outcomes.lock()
+            .unwrap()
+            .clone()
+    }
+
+    pub fn outputs(&self) -> Map<String, Value> {
+        self.outputs.lock()
+            .unwrap()
+            .clone()
+    }
+
+    pub fn backups(&self) -> Vec<Backup> {
+        self.backups.lock()
+            .unwrap()
+            .clone()
+    }
+
+    async fn backup(&self, path: &str) -> Result<()> {
+        let dir = match &self.opts.backup_dir {
+            Some(dir) if !self.opts.dry_run => dir,
+            _ => return Ok(()),
+        };
+
+        // the path is reserved before the backup is created as resources can be applied concurrently
+        let name = {
+            let mut backed_up = self.backed_up.lock().unwrap();
+
+            if backed_up.iter().any(|p| p == path) {
+                return Ok(());
+            }
+
+            backed_up.push(path.to_string());
+            (backed_up.len() - 1).to_string()
+        };
+
+        log::info!("creating backup of: {}", path);
+
+        let backup = Backup::create(dir, &name, path).await?;
+
+        self.backups.lock()
+            .unwrap()
+            .push(backup);
 
         Ok(())
     }
 
-    async fn configure_scripts(&self, _root_dir: &Path, config: &Config) -> Result<()> {
-        for script in &config.scripts {
-            actions::run_script(script).await?;
+    async fn restore(&self) -> Result<()> {
+        let dir = match &self.opts.backup_dir {
+            Some(dir) => dir,
+            None => return Ok(()),
+        };
+
+        for backup in self.backups().iter().rev() {
+            self.emit(Event::FileRestored { path: backup.path.clone() });
+            backup.restore(dir).await?;
+        }
+
+        Ok(())
+    }
+
+    fn notify(&self, names: &[String]) {
+        let mut notified = self.notified.lock().unwrap();
+
+        for name in names {
+            if !notified.contains(name) {
+                log::debug!("notifying handler: {}", name);
+                notified.push(name.clone());
+            }
+        }
+    }
+
+    /// Makes sure the handlers a config notifies are known before any of its resources is applied
+    fn check_notify(&self, name: &str, config: &Config) -> Result<()> {
+        let handlers = self.handlers.lock().unwrap();
+
+        for notify in config.files
+            .iter()
+            .flat_map(|file| &file.notify)
+            .chain(config.scripts.iter().flat_map(|script| &script.notify))
+            .chain(config.resources.iter().flat_map(|resource| &resource.notify)) {
+            if !handlers.iter().any(|(_, handler)| &handler.name == notify) {
+                return Err(Error::Custom(format!("unknown handler {} notified in {}", notify, name)));
+            }
         }
 
         Ok(())
     }
 
-    async fn configure_files(&self, root_dir: &Path, config: &Config) -> Result<()> {
+    async fn run_handlers(&self) -> Result<()> {
+        let handlers = self.handlers.lock()
+            .unwrap()
+            .clone();
+        let notified = self.notified.lock()
+            .unwrap()
+            .clone();
+
+        if !notified.is_empty() {
+            self.emit(Event::StageStarted { stage: Stage::Handlers });
+        }
+
+        for (module, handler) in handlers {
+            if !notified.contains(&handler.name) {
+                continue;
+            }
+
+            let (duration, result) = timed(async {
+                if !self.opts.dry_run {
+                    self.emit(Event::ResourceChanging {
+                        module: module.clone(),
+                        kind: ResourceType::Handler,
+                        id: handler.name.clone(),
+                        change: Change::Handler { name: handler.name.clone() },
+                    });
+                    actions::run_handler(&handler).await?;
+                }
+
+                Ok(Status::Changed)
+            }).await;
+
+            self.finish(&module, ResourceType::Handler, &handler.name, duration, result)?;
+            self.record(&module, Change::Handler { name: handler.name.clone() });
+        }
+
+        Ok(())
+    }
+
+    async fn fetch_source(&self, root_dir: &Path, name: &str, source: &ModuleSource) -> Result<PathBuf> {
+        let url = match source.git.contains("://") || source.git.contains('@') {
+            true => source.git.clone(),
+            false => root_dir.join(&source.git).to_string_lossy().to_string(),
+        };
+        let locked = self.lockfile.lock()
+            .unwrap()
+            .locked_commit(name, source)
+            .map(|commit| commit.to_string());
+
+        let (dir, commit) = actions::fetch_repository(
+            &url,
+            source.reference.as_deref(),
+            locked.as_deref(),
+        ).await?;
+
+        log::info!("using commit {} for module: {}", commit, name);
+
+        self.lockfile.lock()
+            .unwrap()
+            .lock(name, source, &commit);
+
+        Ok(match &source.path {
+            Some(path) => dir.join(path),
+            None => dir,
+        })
+    }
+
+    fn record(&self, name: &str, change: Change) {
+        log::debug!("{}: {}", name, change);
+
+        self.changes.lock()
+            .unwrap()
+            .push(Record {
+                module: name.to_string(),
+                change,
+            });
+    }
+
+    fn condition_met(&self, when: Option<&str>, vars: &Value) -> Result<bool> {
+        let when = match when {
+            Some(when) => when,
+            None => return Ok(true),
+        };
+
+        let mut context = template_context(&self.opts.facts)?;
+
+        context.insert("vars", vars);
+        context.insert("modules", &self.outputs());
+
+        let result = Tera::one_off(
+            &format!("{{% if {} %}}true{{% endif %}}", when),
+            &context,
+            false,
+        ).map_err(|e| Error::from_template_err(format!("when: {}", when), e))?;
+
+        log::debug!("condition {} evaluated to: {}", when, result == "true");
+
+        Ok(result == "true")
+    }
+
+    fn included(&self, scope: &Scope, tags: &[String]) -> bool {
+        if !scope.selected {
+            return false;
+        }
+
+        let tagged = |names: &[String]| scope.tags
+            .iter()
+            .chain(tags)
+            .any(|tag| names.contains(tag));
+
+        if !self.opts.tags.is_empty() && !tagged(&self.opts.tags) {
+            return false;
+        }
+
+        !tagged(&self.opts.skip_tags)
+    }
+
+    fn jobs(&self) -> usize {
+        self.opts.jobs.max(1)
+    }
+
+    fn outcome(&self, module: &str, kind: ResourceType, id: &str, duration: Duration, status: Status, error: Option<String>) {
+        let outcome = Outcome {
+            kind,
+            id: id.to_string(),
+            module: module.to_string(),
+            status,
+            duration: duration.as_secs_f64(),
+            error,
+        };
+
+        self.outcomes.lock()
+            .unwrap()
+            .push(outcome.clone());
+        self.emit(Event::ResourceFinished(outcome));
+    }
+
+    fn finish(&self, module: &str, kind: ResourceType, id: &str, duration: Duration, result: Result<Status>) -> Result<Status> {
+        match &result {
+            Ok(status) => self.outcome(module, kind, id, duration, *status, None),
+            Err(e) => self.outcome(module, kind, id, duration, Status::Failed, Some(e.to_string())),
+        }
+
+        result
+    }
+
+    fn finish_resource(&self, module: &str, resource: &dyn Resource, duration: Duration, result: Result<Status>) -> Result<Status> {
+        let (status, error) = match &result {
+            Ok(status) => (*status, None),
+            Err(e) => (Status::Failed, Some(e.to_string())),
+        };
+
+        for (id, status) in resource.outcomes(status) {
+            let error = match status {
+                Status::Failed => error.clone(),
+                _ => None,
+            };
+
+            self.outcome(module, resource.kind(), &id, duration, status, error);
+        }
+
+        result
+    }
+
+    fn skip(&self, module: &str, kind: ResourceType, id: &str, reason: &str, duration: Duration) {
+        self.emit(Event::ResourceSkipped {
+            module: module.to_string(),
+            kind: kind.clone(),
+            id: id.to_string(),
+            reason: reason.to_string(),
+        });
+        self.outcome(module, kind, id, duration, Status::Skipped, None);
+    }
+
+    fn resources<'a>(&self, name: &str, root_dir: &Path, config: &'a Config, scope: &Scope) -> Result<Vec<Box<dyn Resource + 'a>>> {
+        let mut resources: Vec<Box<dyn Resource + 'a>> = vec![];
+
+        for repo in &config.apt.repositories {
+            resources.push(Box::new(RepositoryResource::new(repo)));
+        }
+
+        let mut names = vec![];
+        let mut tags = vec![];
+
+        for package in &config.apt.packages {
+            if !self.included(scope, package.tags()) {
+                continue;
+            }
+
+            match self.condition_met(package.when(), &scope.vars)? {
+                true => {
+                    names.push(package.name().to_string());
+                    tags.extend(package.tags()
+                        .iter()
+                        .filter(|tag| !tags.contains(*tag))
+                        .cloned()
+                        .collect::<Vec<_>>());
+                },
+                false => self.skip(name, ResourceType::AptPackages, package.name(), "condition not met", Duration::default()),
+            }
+        }
+
+        if !names.is_empty() {
+            resources.push(Box::new(PackagesResource::new(names, tags)));
+        }
+
+        for script in &config.scripts {
+            resources.push(Box::new(ScriptResource::new(script)));
+        }
+
         for file in &config.files {
-            let updated = match file.source() {
-                TemplateSource::Local(template) => {
-                    let mut src = PathBuf::from(root_dir);
-                    src.push(&template);
+            let mut context = file.context.clone();
+
+            if !context.contains_key("modules") {
+                context.insert("modules".to_string(), Value::Object(self.outputs()));
+            }
+
+            if !context.contains_key("facts") {
+                context.insert("facts".to_string(), Value::Object(self.opts.facts.clone()));
+            }
+
+            let bucket = file.s3
+                .as_ref()
+                .and_then(|s3file| config.s3.buckets
+                    .iter()
+                    .find(|bucket| bucket.name == s3file.bucket_name));
+
+            resources.push(Box::new(FileResource::new(
+                file,
+                root_dir.to_path_buf(),
+                context,
+                bucket,
+                self.opts.skip_downloads,
+            )));
+        }
 
-                    log::info!("reading local template file: {:?}", src);
+        for resource in &config.resources {
+            resources.push(Box::new(PluginResource::new(resource, scope.plugin_path.clone())));
+        }
 
-                    let contents = tokio::fs::read(&src).await?;
+        Ok(resources)
+    }
 
-                    actions::write_template(
-                        &template,
-                        &file.path,
-                        str::from_utf8(&contents)?,
-                        file.context.clone(),
-                    ).await?
+    async fn apply_resource(&self, name: &str, resource: &mut dyn Resource) -> Result<Status> {
+        let ctx = resources::Context {
+            diff: self.opts.diff,
+            staging: self.staging.as_ref(),
+        };
+        let changed = resource.check(&ctx).await?;
+
+        self.emit(Event::ResourceChecked {
+            module: name.to_string(),
+            kind: resource.kind(),
+            id: resource.id(),
+            changed,
+        });
+
+        if !changed {
+            return Ok(Status::Unchanged);
+        }
+
+        if let Some(diff) = resource.diff() {
+            self.emit(Event::Diff {
+                path: resource.id(),
+                diff: diff.to_string(),
+            });
+        }
+
+        if self.opts.dry_run {
+            return Ok(Status::Changed);
+        }
+
+        for path in resource.backups() {
+            self.backup(&path).await?;
+        }
+
+        self.emit(Event::ResourceChanging {
+            module: name.to_string(),
+            kind: resource.kind(),
+            id: resource.id(),
+            change: resource.describe(),
+        });
+        resource.apply(&ctx).await?;
+
+        Ok(Status::Changed)
+    }
+
+    async fn configure_resources(&self, name: &str, resources: Vec<Box<dyn Resource + '_>>, scope: &Scope) -> Result<()> {
+        let mut selected = vec![];
+
+        for resource in resources {
+            if !self.included(scope, resource.tags()) {
+                continue;
+            }
+
+            match self.condition_met(resource.when(), &scope.vars)? {
+                true => selected.push(resource),
+                false => self.skip(name, resource.kind(), &resource.id(), "condition not met", Duration::default()),
+            }
+        }
+
+        // the desired state (such as rendered templates and downloads) is computed concurrently
+        let results = stream::iter(selected
+                .iter_mut()
+                .map(|resource| timed(resource.desired()))
+                .collect::<Vec<_>>())
+            .buffered(self.jobs())
+            .collect::<Vec<_>>()
+            .await;
+
+        let mut batches: Vec<Vec<(Duration, Box<dyn Resource + '_>)>> = vec![];
+
+        for (resource, (duration, result)) in selected.into_iter().zip(results) {
+            match result {
+                Ok(true) => {},
+                Ok(false) => {
+                    self.skip(name, resource.kind(), &resource.id(), "desired state unavailable", duration);
+                    continue;
                 },
-                TemplateSource::S3(s3file) => {
-                    if self.skip_downloads {
-                        println!("skipping download for: s3/{}/{}", s3file.bucket_name, s3file.path);
-                        continue;
-                    }
-
-                    log::info!("downloading remote file: s3/{}/{}", s3file.bucket_name, s3file.path);
-
-                    let contents = actions::download_file(
-                        s3file.path.clone(),
-                        config.s3.buckets
-                            .iter()
-                            .filter(|b| b.name == s3file.bucket_name)
-                            .next()
-                            .unwrap(),
-                    ).await?;
-
-                    actions::write_file(
-                        &file.path,
-                        &contents,
-                    ).await?
+                Err(e) => {
+                    self.finish_resource(name, resource.as_ref(), duration, Err(e))?;
+                    continue;
                 },
-            };
+            }
 
-            if updated {
-                if let Some((uid, gid)) = file.owner {
-                    log::info!("changing ownership of: {} to {}:{}", file.path, uid, gid);
-                    chown(&file.path, uid, gid).await?;
-                }
+            // consecutive resources which are safe to run in parallel are applied together
+            match batches.last_mut() {
+                Some(batch) if resource.parallel() && batch.iter().all(|(_, r)| r.parallel()) => batch.push((duration, resource)),
+                _ => batches.push(vec![(duration, resource)]),
+            }
+        }
+
+        for mut batch in batches {
+            let results = stream::iter(batch
+                    .iter_mut()
+                    .map(|(_, resource)| timed(self.apply_resource(name, resource.as_mut())))
+                    .collect::<Vec<_>>())
+                .buffered(self.jobs())
+                .collect::<Vec<_>>()
+                .await;
 
-                if let Some(mode) = file.mode {
-                    log::info!("changing mode of: {} to {}", file.path, mode);
-                    chmod(&file.path, mode).await?;
+            // every resource of the batch is finished first as the others might already be applied
+            let mut error = None;
+
+            for ((desired_duration, resource), (duration, result)) in batch.iter().zip(results) {
+                match self.finish_resource(name, resource.as_ref(), *desired_duration + duration, result) {
+                    Ok(Status::Changed) => {
+                        self.record(name, resource.describe());
+                        self.notify(resource.notify());
+                    },
+                    Ok(_) => {},
+                    Err(e) => if error.is_none() {
+                        error = Some(e);
+                    },
                 }
             }
+
+            if let Some(e) = error {
+                return Err(e);
+            }
         }
 
         Ok(())
     }
 
     #[async_recursion]
-    pub async fn configure_modules(&self, _root_dir: &Path, config: &Config) -> Result<()> {
-        for (name, config) in &config.modules {
-            log::info!("loading module: {}", name);
-            println!(">>> configuring module {}", name);
+    async fn configure_modules(&self, root_dir: &Path, search_path: &[PathBuf], config: &Config, parent: &Scope) -> Result<()> {
+        let mut modules: Vec<LoadedModule> = vec![];
+
+        for instance in config.module_instances() {
+            let name = instance.name();
+
+            if !self.condition_met(instance.when.as_deref(), &parent.vars)? {
+                self.skip(name, ResourceType::Module, name, "condition not met", Duration::default());
+                continue;
+            }
+
+            log::info!("loading module: {} ({})", name, instance.module);
+
+            if modules.iter().any(|loaded| loaded.name == name) {
+                return Err(Error::Custom(format!("module {} is configured more than once", name)));
+            }
 
-            let (module_root, module) = load_module(name, config.clone()).await?;
+            let module_search_path = match config.module_sources.get(&instance.module) {
+                Some(source) => vec![self.fetch_source(root_dir, &instance.module, source).await?],
+                None => search_path.to_vec(),
+            };
+
+            let mut file = read_module(&module_search_path, &instance.module).await?;
+            let vars = file.prepare_vars(instance.vars.clone())?;
+
+            file.definition.depends_on.extend(instance.depends_on.iter().cloned());
+            file.definition.tags.extend(instance.tags.iter().cloned());
+            modules.push(LoadedModule {
+                name: name.to_string(),
+                source: instance.module.clone(),
+                file,
+                vars,
+            });
+        }
+
+        for LoadedModule { name, source, file, vars } in sort_modules(modules)? {
+            self.emit(Event::ModuleEntered {
+                name: name.clone(),
+                source: source.clone(),
+            });
+
+            let outputs = self.outputs();
+            let (module_root, module) = file.render(&vars, &outputs, &self.opts.facts)?;
 
-            self.run(
+            self.outputs.lock()
+                .unwrap()
+                .insert(name.clone(), Value::Object(module.module.outputs.clone()));
+
+            self.modules.lock()
+                .unwrap()
+                .push(name.clone());
+
+            let module_search_path = module.config.module_paths
+                .iter()
+                .map(|path| module_root.join(path))
+                .chain(search_path.iter().cloned())
+                .collect::<Vec<_>>();
+
+            self.configure(
+                &name,
                 &module_root,
+                &module_search_path,
                 &module.config,
+                &Scope {
+                    tags: parent.tags
+                        .iter()
+                        .chain(&file.definition.tags)
+                        .cloned()
+                        .collect(),
+                    selected: parent.selected
+                        || self.opts.only.contains(&name)
+                        || self.opts.only.contains(&source),
+                    plugin_path: plugins::search_path(&module_root, &module.config, Some(&parent.plugin_path)),
+                    vars,
+                },
             ).await?;
         }
 
@@ -114,18 +660,94 @@ impl Provisioner {
     }
 
     #[async_recursion]
-    pub async fn run(&self, root_dir: &Path, config: &Config) -> Result<()> {
-        self.configure_modules(root_dir, config).await?;
+    async fn configure(&self, name: &str, root_dir: &Path, search_path: &[PathBuf], config: &Config, scope: &Scope) -> Result<()> {
+        self.handlers.lock()
+            .unwrap()
+            .extend(config.handlers
+                .iter()
+                .map(|handler| (name.to_string(), handler.clone())));
 
-        println!(">>> configuring apt");
-        self.configure_apt(root_dir, config).await?;
+        self.check_notify(name, config)?;
+        self.configure_modules(root_dir, search_path, config, scope).await?;
 
-        println!(">>> configuring scripts");
-        self.configure_scripts(root_dir, config).await?;
+        let resources = self.resources(name, root_dir, config, scope)?;
 
-        println!(">>> configuring files");
-        self.configure_files(root_dir, config).await?;
+        self.configure_resources(name, resources, scope).await?;
 
         Ok(())
     }
+
+    /// Applies the config, or only checks which changes would be made when `dry_run` is set
+    pub async fn run(&self, name: &str, root_dir: &Path, config: &Config) -> Result<()> {
+        self.emit(Event::RunStarted {
+            config: name.to_string(),
+            dry_run: self.opts.dry_run,
+        });
+
+        let (duration, result) = timed(self.execute(name, root_dir, config)).await;
+
+        self.emit(Event::RunFinished {
+            dry_run: self.opts.dry_run,
+            success: result.is_ok(),
+            error: result.as_ref().err().map(|e| e.to_string()),
+            duration: duration.as_secs_f64(),
+        });
+
+        result
+    }
+
+    async fn execute(&self, name: &str, root_dir: &Path, config: &Config) -> Result<()> {
+        if self.opts.atomic && self.staging.is_none() {
+            return Err(Error::Custom("atomic applies require a backup directory".to_string()));
+        }
+
+        if let Some(path) = &self.opts.lockfile {
+            let lockfile = Lockfile::load(path).await?;
+
+            *self.lockfile.lock().unwrap() = lockfile;
+        }
+
+        let scope = Scope {
+            vars: Value::Object(self.opts.vars.clone()),
+            tags: vec![],
+            selected: self.opts.only.is_empty(),
+            plugin_path: plugins::search_path(root_dir, config, None),
+        };
+        let mut result = self.configure(name, root_dir, &search_path(root_dir, config), config, &scope).await;
+
+        if let (Ok(_), Some(path), false) = (&result, &self.opts.lockfile, self.opts.dry_run) {
+            let lockfile = std::mem::take(&mut *self.lockfile.lock().unwrap());
+
+            result = lockfile.save(path).await;
+        }
+
+        let staging = match &self.staging {
+            Some(staging) => staging,
+            None => {
+                if result.is_ok() {
+                    result = self.run_handlers().await;
+                }
+
+                return result;
+            },
+        };
+
+        if result.is_ok() {
+            self.emit(Event::StageStarted { stage: Stage::Committing });
+            result = staging.commit(|path| self.emit(Event::FileUpdated { path: path.to_string() })).await;
+        }
+
+        if result.is_ok() {
+            result = self.run_handlers().await;
+        }
+
+        staging.cleanup().await?;
+
+        if result.is_err() {
+            self.emit(Event::StageStarted { stage: Stage::Restoring });
+            self.restore().await?;
+        }
+
+        result
+    }
 }
@@ -0,0 +1,114 @@
+use std::fmt;
+use std::result;
+use std::str::FromStr;
+use std::path::Path;
+
+use tokio::fs;
+use serde::{Serialize, Serializer};
+
+use super::result::Result;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Output {
+    Text,
+    Compact,
+    JsonLines,
+    Json,
+    Quiet,
+}
+
+impl FromStr for Output {
+    type Err = String;
+
+    fn from_str(s: &str) -> result::Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(Self::Text),
+            "compact" => Ok(Self::Compact),
+            "jsonl" => Ok(Self::JsonLines),
+            "json" => Ok(Self::Json),
+            "quiet" => Ok(Self::Quiet),
+            _ => Err(format!("unknown output {}, expected text, compact, jsonl, json or quiet", s)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ResourceType {
+    AptRepository,
+    AptPackages,
+    Script,
+    File,
+    Handler,
+    Module,
+    Plugin(String),
+}
+
+impl Serialize for ResourceType {
+    fn serialize<S>(&self, serializer: S) -> result::Result<S::Ok, S::Error>
+    where S: Serializer {
+        match self {
+            Self::AptRepository => serializer.serialize_str("aptRepository"),
+            Self::AptPackages => serializer.serialize_str("aptPackages"),
+            Self::Script => serializer.serialize_str("script"),
+            Self::File => serializer.serialize_str("file"),
+            Self::Handler => serializer.serialize_str("handler"),
+            Self::Module => serializer.serialize_str("module"),
+            Self::Plugin(kind) => serializer.serialize_str(&format!("plugin:{}", kind)),
+        }
+    }
+}
+
+impl fmt::Display for ResourceType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::AptRepository => write!(f, "apt repository"),
+            Self::AptPackages => write!(f, "apt packages"),
+            Self::Script => write!(f, "script"),
+            Self::File => write!(f, "file"),
+            Self::Handler => write!(f, "handler"),
+            Self::Module => write!(f, "module"),
+            Self::Plugin(kind) => write!(f, "plugin:{}", kind),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Status {
+    Unchanged,
+    Changed,
+    Skipped,
+    Failed,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Outcome {
+    #[serde(rename = "type")]
+    pub kind: ResourceType,
+    pub id: String,
+    pub module: String,
+    pub status: Status,
+    pub duration: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Report {
+    pub config: String,
+    pub run: Option<String>,
+    pub dry_run: bool,
+    pub success: bool,
+    pub error: Option<String>,
+    pub duration: f64,
+    pub resources: Vec<Outcome>,
+}
+
+impl Report {
+    pub async fn save(&self, path: &Path) -> Result<()> {
+        fs::write(path, serde_json::to_string_pretty(self)?).await?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,218 @@
+use std::fs::File;
+use std::sync::Mutex;
+use std::io::{self, Write};
+
+use super::ioutils::is_tty;
+use super::report::Status;
+use super::events::{Event, Observer, Stage};
+
+fn print_diff(diff: &str) {
+    let color = is_tty();
+
+    for line in diff.lines() {
+        let code = if line.starts_with("---") || line.starts_with("+++") {
+            ""
+        } else if line.starts_with('@') {
+            "\x1b[36m"
+        } else if line.starts_with('-') {
+            "\x1b[31m"
+        } else if line.starts_with('+') {
+            "\x1b[32m"
+        } else {
+            ""
+        };
+
+        match color && !code.is_empty() {
+            true => println!("{}{}\x1b[0m", code, line),
+            false => println!("{}", line),
+        }
+    }
+}
+
+/// Prints every step of a run, this is the default output of compute.
+#[derive(Default)]
+pub struct TextReporter {
+    dry_run: Mutex<bool>,
+}
+
+impl Observer for TextReporter {
+    fn on_event(&self, event: &Event) {
+        match event {
+            Event::RunStarted { dry_run, .. } => {
+                *self.dry_run.lock().unwrap() = *dry_run;
+
+                match dry_run {
+                    true => println!(">> planning"),
+                    false => println!(">> provisioning"),
+                }
+            },
+            Event::ModuleEntered { name, source } => match name == source {
+                true => println!(">>> configuring module {}", name),
+                false => println!(">>> configuring module {} ({})", name, source),
+            },
+            Event::ResourceChecked { kind, id, changed: false, .. } if !*self.dry_run.lock().unwrap() =>
+                println!("no changes found for {}: {}", kind, id),
+            Event::ResourceChanging { change, .. } => println!("{}", change),
+            Event::ResourceSkipped { kind, id, reason, .. } => println!("skipping ({}): {} {}", reason, kind, id),
+            Event::Diff { diff, .. } => print_diff(diff),
+            Event::StageStarted { stage } => match stage {
+                Stage::Handlers => println!(">>> running handlers"),
+                Stage::Committing => println!(">>> committing files"),
+                Stage::Restoring => println!(">>> restoring files"),
+            },
+            Event::FileUpdated { path } => println!("updating file: {}", path),
+            Event::FileRestored { path } => println!("restoring file: {}", path),
+            Event::RunFinished { dry_run, error, .. } => match (dry_run, error) {
+                (false, None) => println!(">> finished"),
+                (false, Some(error)) => println!("provisioning failed:\n  {}", error),
+                (true, Some(error)) => println!("planning failed:\n  {}", error),
+                (true, None) => {},
+            },
+            _ => {},
+        }
+    }
+}
+
+#[derive(Default)]
+struct Progress {
+    module: String,
+    changed: usize,
+    unchanged: usize,
+    skipped: usize,
+    failed: usize,
+}
+
+impl Progress {
+    fn summary(&self) -> String {
+        format!(
+            "{} changed, {} unchanged, {} skipped, {} failed",
+            self.changed,
+            self.unchanged,
+            self.skipped,
+            self.failed,
+        )
+    }
+}
+
+/// Keeps a single status line with the number of resources per status, only failures and
+/// the result of the run are printed on their own line.
+pub struct ProgressReporter {
+    tty: bool,
+    progress: Mutex<Progress>,
+}
+
+impl ProgressReporter {
+    pub fn new() -> Self {
+        Self {
+            tty: is_tty(),
+            progress: Mutex::new(Progress::default()),
+        }
+    }
+
+    fn clear(&self) {
+        if self.tty {
+            print!("\r\x1b[K");
+        }
+    }
+
+    fn draw(&self, progress: &Progress) {
+        if self.tty {
+            print!("\r\x1b[K[{}] {}", progress.module, progress.summary());
+            let _ = io::stdout().flush();
+        }
+    }
+}
+
+impl Default for ProgressReporter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Observer for ProgressReporter {
+    fn on_event(&self, event: &Event) {
+        let mut progress = self.progress.lock().unwrap();
+
+        match event {
+            Event::ModuleEntered { name, .. } => progress.module = name.clone(),
+            Event::ResourceFinished(outcome) => match outcome.status {
+                Status::Changed => progress.changed += 1,
+                Status::Unchanged => progress.unchanged += 1,
+                Status::Skipped => progress.skipped += 1,
+                Status::Failed => {
+                    progress.failed += 1;
+
+                    self.clear();
+                    println!(
+                        "failed {} {}: {}",
+                        outcome.kind,
+                        outcome.id,
+                        outcome.error.as_deref().unwrap_or_default(),
+                    );
+                },
+            },
+            Event::RunFinished { dry_run, error, duration, .. } => {
+                self.clear();
+                println!(
+                    "{} in {:.1}s: {}",
+                    match dry_run {
+                        true => "planned",
+                        false => "provisioned",
+                    },
+                    duration,
+                    progress.summary(),
+                );
+
+                if let Some(error) = error {
+                    println!("failed:\n  {}", error);
+                }
+
+                return;
+            },
+            _ => return,
+        }
+
+        self.draw(&progress);
+    }
+}
+
+/// Writes every event as a JSON object on its own line.
+pub struct JsonLinesReporter {
+    out: Mutex<File>,
+}
+
+impl JsonLinesReporter {
+    pub fn new(out: File) -> Self {
+        Self {
+            out: Mutex::new(out),
+        }
+    }
+}
+
+impl Observer for JsonLinesReporter {
+    fn on_event(&self, event: &Event) {
+        let mut out = self.out.lock().unwrap();
+
+        match serde_json::to_string(event) {
+            Ok(json) => {
+                let _ = writeln!(out, "{}", json);
+                let _ = out.flush();
+            },
+            Err(e) => log::error!("failed to serialize event: {}", e),
+        }
+    }
+}
+
+/// Prints nothing but the error of a failed run.
+pub struct QuietReporter;
+
+impl Observer for QuietReporter {
+    fn on_event(&self, event: &Event) {
+        if let Event::RunFinished { dry_run, error: Some(error), .. } = event {
+            match dry_run {
+                true => eprintln!("planning failed:\n  {}", error),
+                false => eprintln!("provisioning failed:\n  {}", error),
+            }
+        }
+    }
+}
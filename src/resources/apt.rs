@@ -0,0 +1,113 @@
+use async_trait::async_trait;
+
+use crate::actions;
+use crate::result::Result;
+use crate::changes::Change;
+use crate::report::{ResourceType, Status};
+use crate::config::apt::AptRepository;
+use super::{Resource, Context};
+
+pub struct RepositoryResource<'a> {
+    repo: &'a AptRepository,
+}
+
+impl<'a> RepositoryResource<'a> {
+    pub fn new(repo: &'a AptRepository) -> Self {
+        Self { repo }
+    }
+}
+
+#[async_trait]
+impl Resource for RepositoryResource<'_> {
+    fn kind(&self) -> ResourceType {
+        ResourceType::AptRepository
+    }
+
+    fn id(&self) -> String {
+        self.repo.name.clone()
+    }
+
+    fn describe(&self) -> Change {
+        Change::Repository { name: self.repo.name.clone() }
+    }
+
+    fn when(&self) -> Option<&str> {
+        self.repo.when.as_deref()
+    }
+
+    fn backups(&self) -> Vec<String> {
+        let (list_file, key_file) = actions::repository_files(self.repo);
+
+        vec![list_file, key_file]
+    }
+
+    fn parallel(&self) -> bool {
+        true
+    }
+
+    async fn check(&mut self, _ctx: &Context<'_>) -> Result<bool> {
+        actions::repository_changed(self.repo).await
+    }
+
+    async fn apply(&self, _ctx: &Context<'_>) -> Result<()> {
+        actions::add_repository(self.repo).await?;
+
+        Ok(())
+    }
+}
+
+pub struct PackagesResource {
+    names: Vec<String>,
+    missing: Vec<String>,
+    tags: Vec<String>,
+}
+
+impl PackagesResource {
+    /// Takes the packages which are included in the run and the union of their tags.
+    pub fn new(names: Vec<String>, tags: Vec<String>) -> Self {
+        Self {
+            missing: names.clone(),
+            names,
+            tags,
+        }
+    }
+}
+
+#[async_trait]
+impl Resource for PackagesResource {
+    fn kind(&self) -> ResourceType {
+        ResourceType::AptPackages
+    }
+
+    fn id(&self) -> String {
+        self.names.join(", ")
+    }
+
+    fn describe(&self) -> Change {
+        Change::Packages { names: self.missing.clone() }
+    }
+
+    fn tags(&self) -> &[String] {
+        &self.tags
+    }
+
+    fn outcomes(&self, status: Status) -> Vec<(String, Status)> {
+        self.names
+            .iter()
+            .map(|name| (name.clone(), match status {
+                Status::Changed | Status::Failed if !self.missing.contains(name) => Status::Unchanged,
+                status => status,
+            }))
+            .collect()
+    }
+
+    async fn check(&mut self, _ctx: &Context<'_>) -> Result<bool> {
+        self.missing = actions::missing_packages(&self.names).await?;
+
+        Ok(!self.missing.is_empty())
+    }
+
+    async fn apply(&self, _ctx: &Context<'_>) -> Result<()> {
+        actions::install_packages(&self.missing).await
+    }
+}
@@ -0,0 +1,150 @@
+use std::str;
+use std::path::PathBuf;
+
+use tera::{Map, Value};
+use async_trait::async_trait;
+
+use crate::actions;
+use crate::result::{Result, Error};
+use crate::changes::Change;
+use crate::report::ResourceType;
+use crate::ioutils::{chmod, chown};
+use crate::config::s3::Bucket;
+use crate::config::files::{TemplateFile, TemplateSource};
+use super::{Resource, Context};
+
+pub struct FileResource<'a> {
+    file: &'a TemplateFile,
+    root_dir: PathBuf,
+    context: Map<String, Value>,
+    bucket: Option<&'a Bucket>,
+    skip_downloads: bool,
+    contents: Vec<u8>,
+    checksum: Option<u32>,
+    diff: Option<String>,
+}
+
+impl<'a> FileResource<'a> {
+    pub fn new(
+        file: &'a TemplateFile,
+        root_dir: PathBuf,
+        context: Map<String, Value>,
+        bucket: Option<&'a Bucket>,
+        skip_downloads: bool,
+    ) -> Self {
+        Self {
+            file,
+            root_dir,
+            context,
+            bucket,
+            skip_downloads,
+            contents: vec![],
+            checksum: None,
+            diff: None,
+        }
+    }
+}
+
+#[async_trait]
+impl Resource for FileResource<'_> {
+    fn kind(&self) -> ResourceType {
+        ResourceType::File
+    }
+
+    fn id(&self) -> String {
+        self.file.path.clone()
+    }
+
+    fn describe(&self) -> Change {
+        Change::File {
+            path: self.file.path.clone(),
+            checksum: self.checksum,
+        }
+    }
+
+    fn when(&self) -> Option<&str> {
+        self.file.when.as_deref()
+    }
+
+    fn tags(&self) -> &[String] {
+        &self.file.tags
+    }
+
+    fn notify(&self) -> &[String] {
+        &self.file.notify
+    }
+
+    fn backups(&self) -> Vec<String> {
+        vec![self.file.path.clone()]
+    }
+
+    async fn desired(&mut self) -> Result<bool> {
+        self.contents = match self.file.source() {
+            TemplateSource::Local(template) => {
+                let src = self.root_dir.join(template);
+
+                log::info!("reading local template file: {:?}", src);
+
+                let contents = tokio::fs::read(&src).await?;
+
+                actions::render_template(
+                    template,
+                    str::from_utf8(&contents)?,
+                    self.context.clone(),
+                )?.into_bytes()
+            },
+            TemplateSource::S3(s3file) => {
+                if self.skip_downloads {
+                    log::info!("skipping download for: s3/{}/{}", s3file.bucket_name, s3file.path);
+                    return Ok(false);
+                }
+
+                log::info!("downloading remote file: s3/{}/{}", s3file.bucket_name, s3file.path);
+
+                let bucket = match self.bucket {
+                    Some(bucket) => bucket,
+                    None => return Err(Error::Custom(format!("unknown bucket {}", s3file.bucket_name))),
+                };
+
+                actions::download_file(s3file.path.clone(), bucket).await?
+            },
+        };
+        self.checksum = actions::file_checksum(&self.file.path).await?;
+
+        Ok(true)
+    }
+
+    fn diff(&self) -> Option<&str> {
+        self.diff.as_deref()
+    }
+
+    async fn check(&mut self, ctx: &Context<'_>) -> Result<bool> {
+        if !ctx.diff {
+            return actions::file_changed(&self.file.path, &self.contents).await;
+        }
+
+        self.diff = actions::file_diff(&self.file.path, &self.contents).await?;
+
+        Ok(self.diff.is_some())
+    }
+
+    async fn apply(&self, ctx: &Context<'_>) -> Result<()> {
+        if let Some(staging) = ctx.staging {
+            return staging.stage(&self.file.path, &self.contents, self.file.owner, self.file.mode).await;
+        }
+
+        actions::write_file(&self.file.path, &self.contents).await?;
+
+        if let Some((uid, gid)) = self.file.owner {
+            log::info!("changing ownership of: {} to {}:{}", self.file.path, uid, gid);
+            chown(&self.file.path, uid, gid).await?;
+        }
+
+        if let Some(mode) = self.file.mode {
+            log::info!("changing mode of: {} to {}", self.file.path, mode);
+            chmod(&self.file.path, mode).await?;
+        }
+
+        Ok(())
+    }
+}
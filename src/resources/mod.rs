@@ -0,0 +1,77 @@
+use async_trait::async_trait;
+
+use crate::result::Result;
+use crate::changes::Change;
+use crate::staging::Staging;
+use crate::report::{ResourceType, Status};
+
+mod apt;
+mod file;
+mod script;
+mod plugin;
+
+pub use apt::{RepositoryResource, PackagesResource};
+pub use file::FileResource;
+pub use script::ScriptResource;
+pub use plugin::PluginResource;
+
+pub struct Context<'a> {
+    pub diff: bool,
+    pub staging: Option<&'a Staging>,
+}
+
+/// Something on the system which is managed by compute such as a file, a script or a set of packages.
+#[async_trait]
+pub trait Resource: Send + Sync {
+    fn kind(&self) -> ResourceType;
+
+    fn id(&self) -> String;
+
+    /// The change which is made when the resource is applied, used for plans and the history.
+    fn describe(&self) -> Change;
+
+    fn when(&self) -> Option<&str> {
+        None
+    }
+
+    /// The outcomes which are reported for the resource, a resource which manages several
+    /// items (such as a set of packages) reports one per item.
+    fn outcomes(&self, status: Status) -> Vec<(String, Status)> {
+        vec![(self.id(), status)]
+    }
+
+    fn tags(&self) -> &[String] {
+        &[]
+    }
+
+    fn notify(&self) -> &[String] {
+        &[]
+    }
+
+    /// Files which should be backed up before the resource is applied.
+    fn backups(&self) -> Vec<String> {
+        vec![]
+    }
+
+    /// Whether the resource can be checked and applied at the same time as its neighbours.
+    fn parallel(&self) -> bool {
+        false
+    }
+
+    /// Computes the desired state (for example by rendering a template), returns false if it
+    /// can't be computed in this run.
+    async fn desired(&mut self) -> Result<bool> {
+        Ok(true)
+    }
+
+    /// Shows how the current state differs from the desired state after a check, if the
+    /// resource supports it.
+    fn diff(&self) -> Option<&str> {
+        None
+    }
+
+    /// Compares the current state with the desired state, returns true if they differ.
+    async fn check(&mut self, ctx: &Context<'_>) -> Result<bool>;
+
+    async fn apply(&self, ctx: &Context<'_>) -> Result<()>;
+}
@@ -0,0 +1,100 @@
+use std::path::PathBuf;
+
+use serde_json::json;
+use async_trait::async_trait;
+
+use crate::actions;
+use crate::result::{Result, Error};
+use crate::changes::Change;
+use crate::plugins::find_plugin;
+use crate::report::ResourceType;
+use crate::config::resources::CustomResource;
+use super::{Resource, Context};
+
+/// A resource implemented by an external executable named `compute-resource-<type>`. For both
+/// commands the plugin receives a JSON request (`{"command": "check" | "apply", "name": ..., "state": {...}}`)
+/// on stdin and answers with a JSON object on stdout, `{"changed": true}` for a check of a resource
+/// which isn't in the desired state and `{"error": "..."}` if something went wrong.
+pub struct PluginResource<'a> {
+    resource: &'a CustomResource,
+    search_path: Vec<PathBuf>,
+    executable: Option<PathBuf>,
+}
+
+impl<'a> PluginResource<'a> {
+    pub fn new(resource: &'a CustomResource, search_path: Vec<PathBuf>) -> Self {
+        Self {
+            resource,
+            search_path,
+            executable: None,
+        }
+    }
+
+    async fn call(&self, command: &str) -> Result<serde_json::Value> {
+        let executable = match &self.executable {
+            Some(executable) => executable,
+            None => return Err(Error::Custom(format!("no plugin resolved for resource type {}", self.resource.kind))),
+        };
+
+        actions::run_plugin(executable, self.resource.privileged, &json!({
+            "command": command,
+            "name": self.resource.name,
+            "state": self.resource.state,
+        })).await
+    }
+}
+
+#[async_trait]
+impl Resource for PluginResource<'_> {
+    fn kind(&self) -> ResourceType {
+        ResourceType::Plugin(self.resource.kind.clone())
+    }
+
+    fn id(&self) -> String {
+        self.resource.name.clone()
+    }
+
+    fn describe(&self) -> Change {
+        Change::Resource {
+            kind: self.resource.kind.clone(),
+            name: self.resource.name.clone(),
+        }
+    }
+
+    fn when(&self) -> Option<&str> {
+        self.resource.when.as_deref()
+    }
+
+    fn tags(&self) -> &[String] {
+        &self.resource.tags
+    }
+
+    fn notify(&self) -> &[String] {
+        &self.resource.notify
+    }
+
+    /// The plugin is only looked up once the resource is selected, so that resources which are
+    /// filtered out don't require their plugin to be installed.
+    async fn desired(&mut self) -> Result<bool> {
+        self.executable = Some(find_plugin(&self.search_path, &self.resource.kind)?);
+
+        Ok(true)
+    }
+
+    async fn check(&mut self, _ctx: &Context<'_>) -> Result<bool> {
+        match self.call("check").await?.get("changed") {
+            Some(serde_json::Value::Bool(changed)) => Ok(*changed),
+            _ => Err(Error::Custom(format!(
+                "invalid check response from plugin for {} {}: missing changed",
+                self.resource.kind,
+                self.resource.name,
+            ))),
+        }
+    }
+
+    async fn apply(&self, _ctx: &Context<'_>) -> Result<()> {
+        self.call("apply").await?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,57 @@
+use async_trait::async_trait;
+
+use crate::actions;
+use crate::result::Result;
+use crate::changes::Change;
+use crate::report::ResourceType;
+use crate::config::scripts::Script;
+use super::{Resource, Context};
+
+pub struct ScriptResource<'a> {
+    script: &'a Script,
+}
+
+impl<'a> ScriptResource<'a> {
+    pub fn new(script: &'a Script) -> Self {
+        Self { script }
+    }
+}
+
+#[async_trait]
+impl Resource for ScriptResource<'_> {
+    fn kind(&self) -> ResourceType {
+        ResourceType::Script
+    }
+
+    fn id(&self) -> String {
+        self.script.name.clone()
+    }
+
+    fn describe(&self) -> Change {
+        Change::Script { name: self.script.name.clone() }
+    }
+
+    fn when(&self) -> Option<&str> {
+        self.script.when.as_deref()
+    }
+
+    fn tags(&self) -> &[String] {
+        &self.script.tags
+    }
+
+    fn notify(&self) -> &[String] {
+        &self.script.notify
+    }
+
+    fn parallel(&self) -> bool {
+        self.script.parallel && !self.script.privileged
+    }
+
+    async fn check(&mut self, _ctx: &Context<'_>) -> Result<bool> {
+        Ok(actions::script_needed(self.script).await)
+    }
+
+    async fn apply(&self, _ctx: &Context<'_>) -> Result<()> {
+        actions::run_script(self.script).await
+    }
+}
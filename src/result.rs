@@ -11,6 +11,7 @@ use rusoto_s3::GetObjectError;
 pub type Result<T> = result::Result<T, Error>;
 
 #[derive(Debug)]
+#[allow(clippy::enum_variant_names)]
 pub enum Error {
     Custom(String),
     IOError(io::Error),
@@ -19,6 +20,8 @@ pub enum Error {
     TeraError(tera::Error),
     TeraTemplateError((String, tera::Error)),
     Json5Error(json5::Error),
+    JsonError(serde_json::Error),
+    TomlError(toml::de::Error),
     ReqwestError(reqwest::Error),
     RusotoError(RusotoError<GetObjectError>),
 }
@@ -66,6 +69,8 @@ impl fmt::Display for Error {
             Self::Json5Error(e) => match e {
                 json5::Error::Message(msg) => write!(f, "json5 error: {}", msg),
             },
+            Self::JsonError(e) => write!(f, "json error: {}", e),
+            Self::TomlError(e) => write!(f, "toml error: {}", e),
             Self::ReqwestError(e) => match e.status() {
                 Some(code) => write!(f, "invalid status code {} from response: {}", code.as_str(), e),
                 None => write!(f, "reqwest error: {}", e),
@@ -93,6 +98,18 @@ impl From<json5::Error> for Error {
     }
 }
 
+impl From<serde_json::Error> for Error {
+    fn from(err: serde_json::Error) -> Self {
+        Error::JsonError(err)
+    }
+}
+
+impl From<toml::de::Error> for Error {
+    fn from(err: toml::de::Error) -> Self {
+        Error::TomlError(err)
+    }
+}
+
 impl From<RusotoError<GetObjectError>> for Error {
     fn from(err: RusotoError<GetObjectError>) -> Self {
         Error::RusotoError(err)
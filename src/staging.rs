@@ -0,0 +1,68 @@
+use std::sync::Mutex;
+use std::path::PathBuf;
+
+use tokio::fs;
+
+use super::actions;
+use super::result::Result;
+
+struct StagedFile {
+    path: String,
+    staged: PathBuf,
+    owner: Option<(u32, u32)>,
+    mode: Option<u32>,
+}
+
+pub struct Staging {
+    dir: PathBuf,
+    files: Mutex<Vec<StagedFile>>,
+}
+
+impl Staging {
+    pub fn new(dir: PathBuf) -> Self {
+        Self {
+            dir,
+            files: Mutex::new(vec![]),
+        }
+    }
+
+    pub async fn stage(&self, path: &str, contents: &[u8], owner: Option<(u32, u32)>, mode: Option<u32>) -> Result<()> {
+        fs::create_dir_all(&self.dir).await?;
+
+        let mut files = self.files.lock().unwrap();
+        let staged = self.dir.join(files.len().to_string());
+
+        std::fs::write(&staged, contents)?;
+
+        files.push(StagedFile {
+            path: path.to_string(),
+            staged,
+            owner,
+            mode,
+        });
+
+        Ok(())
+    }
+
+    pub async fn commit(&self, on_update: impl Fn(&str)) -> Result<()> {
+        let staged = self.files.lock()
+            .unwrap()
+            .drain(..)
+            .collect::<Vec<_>>();
+
+        for file in staged {
+            on_update(&file.path);
+            actions::replace_file(&file.staged, &file.path, file.owner, file.mode).await?;
+        }
+
+        Ok(())
+    }
+
+    pub async fn cleanup(&self) -> Result<()> {
+        if self.dir.exists() {
+            fs::remove_dir_all(&self.dir).await?;
+        }
+
+        Ok(())
+    }
+}